@@ -0,0 +1,309 @@
+//! Client for the username chain.
+//!
+//! [`UsernameClient`] talks to a node over WebSocket. Username reads and subscriptions go through
+//! the `usernameStorage_*` methods, and `set_username` transactions are built from the runtime
+//! types and signed locally, so no metadata is needed at run time.
+
+use codec::{Decode, Encode};
+use jsonrpsee::{
+    core::client::{ClientT, Subscription},
+    rpc_params,
+    ws_client::{WsClient, WsClientBuilder},
+};
+use solochain_template_runtime::{
+    self as runtime, AccountId, Hash, Nonce, RuntimeCall, SignedPayload, TxExtension,
+    UncheckedExtrinsic,
+};
+use sp_core::{
+    hashing::{blake2_128, twox_128},
+    sr25519, Bytes, Pair,
+};
+use sp_runtime::generic::Era;
+use sp_version::RuntimeVersion;
+use std::fmt;
+use username_storage_rpc::UsernameStorageRpcApiClient;
+
+pub use username_storage_rpc::{Username, UsernameChange};
+
+/// Name of the username storage pallet in the runtime.
+const PALLET_PREFIX: &[u8] = b"UsernameStorage";
+/// Name of the storage map holding usernames by account.
+const USERNAMES_PREFIX: &[u8] = b"Usernames";
+/// Name of the storage map holding accounts by username.
+const USERNAME_OWNERS_PREFIX: &[u8] = b"UsernameOwners";
+
+/// Number of keys fetched per request when listing usernames, kept within the batch size the
+/// node accepts by default.
+const PAGE_SIZE: u32 = username_storage_rpc::DEFAULT_MAX_BATCH_SIZE as u32;
+
+/// Errors of the username client.
+#[derive(Debug)]
+pub enum Error {
+    /// The RPC request failed
+    Rpc(jsonrpsee::core::ClientError),
+    /// The node returned data that does not decode
+    Codec(codec::Error),
+    /// The node does not know the genesis block
+    MissingGenesis,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Rpc(e) => write!(f, "RPC request failed: {}", e),
+            Self::Codec(e) => write!(f, "Unable to decode node response: {}", e),
+            Self::MissingGenesis => write!(f, "Genesis block not found"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<jsonrpsee::core::ClientError> for Error {
+    fn from(e: jsonrpsee::core::ClientError) -> Self {
+        Self::Rpc(e)
+    }
+}
+
+impl From<codec::Error> for Error {
+    fn from(e: codec::Error) -> Self {
+        Self::Codec(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Storage key prefix of `map` in the username storage pallet.
+fn map_prefix(map: &[u8]) -> Vec<u8> {
+    let mut key = twox_128(PALLET_PREFIX).to_vec();
+    key.extend(twox_128(map));
+    key
+}
+
+/// Storage key of `key` in a `Blake2_128Concat` map of the username storage pallet.
+fn map_key(map: &[u8], key: &impl Encode) -> Vec<u8> {
+    let encoded = key.encode();
+    let mut full = map_prefix(map);
+    full.extend(blake2_128(&encoded));
+    full.extend(encoded);
+    full
+}
+
+/// Build and sign an extrinsic dispatching `call` from `signer`.
+///
+/// The transaction is immortal, so it stays valid until `nonce` is used.
+pub fn signed_extrinsic(
+    signer: &sr25519::Pair,
+    call: RuntimeCall,
+    nonce: Nonce,
+    genesis_hash: Hash,
+    version: &RuntimeVersion,
+) -> UncheckedExtrinsic {
+    let tx_ext: TxExtension = (
+        frame_system::CheckNonZeroSender::<runtime::Runtime>::new(),
+        frame_system::CheckSpecVersion::<runtime::Runtime>::new(),
+        frame_system::CheckTxVersion::<runtime::Runtime>::new(),
+        frame_system::CheckGenesis::<runtime::Runtime>::new(),
+        frame_system::CheckEra::<runtime::Runtime>::from(Era::Immortal),
+        runtime::CheckUsernames::default(),
+        frame_system::CheckNonce::<runtime::Runtime>::from(nonce),
+        frame_system::CheckWeight::<runtime::Runtime>::new(),
+        pallet_username_storage::CheckSponsoredRegistration::<runtime::Runtime>::new(),
+        pallet_skip_feeless_payment::SkipCheckIfFeeless::from(
+            pallet_transaction_payment::ChargeTransactionPayment::<runtime::Runtime>::from(0),
+        ),
+        frame_metadata_hash_extension::CheckMetadataHash::<runtime::Runtime>::new(false),
+        frame_system::WeightReclaim::<runtime::Runtime>::new(),
+    );
+
+    let raw_payload = SignedPayload::from_raw(
+        call.clone(),
+        tx_ext.clone(),
+        (
+            (),
+            version.spec_version,
+            version.transaction_version,
+            genesis_hash,
+            genesis_hash,
+            ((), (), ()),
+            (),
+            (),
+            (),
+            (),
+            None,
+            (),
+        ),
+    );
+    let signature = raw_payload.using_encoded(|e| signer.sign(e));
+
+    UncheckedExtrinsic::new_signed(
+        call,
+        AccountId::from(signer.public()).into(),
+        runtime::Signature::Sr25519(signature),
+        tx_ext,
+    )
+}
+
+/// A connection to a node of the username chain.
+pub struct UsernameClient {
+    rpc: WsClient,
+}
+
+impl UsernameClient {
+    /// Connect to the node listening at `url`, for example `ws://127.0.0.1:9944`.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let rpc = WsClientBuilder::default().build(url).await?;
+        Ok(Self { rpc })
+    }
+
+    /// The underlying RPC client, for methods not covered here.
+    pub fn rpc(&self) -> &WsClient {
+        &self.rpc
+    }
+
+    /// The username of `account`, at the best block unless `at` is given.
+    pub async fn username(&self, account: AccountId, at: Option<Hash>) -> Result<Option<Username>> {
+        Ok(
+            UsernameStorageRpcApiClient::<AccountId, Hash>::get_username(
+                &self.rpc, account, at, None, None,
+            )
+            .await?,
+        )
+    }
+
+    /// The usernames of `accounts`, in the same order.
+    pub async fn usernames(
+        &self,
+        accounts: Vec<AccountId>,
+        at: Option<Hash>,
+    ) -> Result<Vec<Option<Username>>> {
+        Ok(
+            UsernameStorageRpcApiClient::<AccountId, Hash>::get_usernames(
+                &self.rpc, accounts, at, None, None,
+            )
+            .await?,
+        )
+    }
+
+    /// The account owning `username`, if any.
+    pub async fn resolve(&self, username: &[u8], at: Option<Hash>) -> Result<Option<AccountId>> {
+        let key = map_key(USERNAME_OWNERS_PREFIX, &username);
+        let value: Option<Bytes> = self
+            .rpc
+            .request("state_getStorage", rpc_params![Bytes(key), at])
+            .await?;
+        Ok(value
+            .map(|value| AccountId::decode(&mut &value[..]))
+            .transpose()?)
+    }
+
+    /// Every account with a username, along with the username, at the best block.
+    pub async fn all_usernames(&self) -> Result<Vec<(AccountId, Username)>> {
+        // Pin the block so that all pages come from the same state.
+        let at: Hash = self
+            .rpc
+            .request("chain_getBlockHash", rpc_params![])
+            .await?;
+        let prefix = Bytes(map_prefix(USERNAMES_PREFIX));
+        let mut start: Option<Bytes> = None;
+        let mut all = Vec::new();
+
+        loop {
+            let keys: Vec<Bytes> = self
+                .rpc
+                .request(
+                    "state_getKeysPaged",
+                    rpc_params![prefix.clone(), PAGE_SIZE, start.clone(), at],
+                )
+                .await?;
+            let Some(last) = keys.last().cloned() else {
+                break;
+            };
+
+            // Keys end with `blake2_128(account) ++ account`.
+            let accounts = keys
+                .iter()
+                .map(|key| AccountId::decode(&mut &key[prefix.len() + 16..]))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let usernames = self.usernames(accounts.clone(), Some(at)).await?;
+            all.extend(
+                accounts
+                    .into_iter()
+                    .zip(usernames)
+                    .filter_map(|(account, username)| Some((account, username?))),
+            );
+
+            if keys.len() < PAGE_SIZE as usize {
+                break;
+            }
+            start = Some(last);
+        }
+
+        Ok(all)
+    }
+
+    /// Watch the usernames of `accounts`, on new best blocks or only on finalized blocks.
+    pub async fn watch(
+        &self,
+        accounts: Vec<AccountId>,
+        finalized: bool,
+    ) -> Result<Subscription<UsernameChange<AccountId, Hash>>> {
+        Ok(
+            UsernameStorageRpcApiClient::<AccountId, Hash>::subscribe_username(
+                &self.rpc,
+                accounts,
+                Some(finalized),
+                None,
+            )
+            .await?,
+        )
+    }
+
+    /// Sign `call` from `signer` with its next nonce.
+    pub async fn sign(
+        &self,
+        signer: &sr25519::Pair,
+        call: RuntimeCall,
+    ) -> Result<UncheckedExtrinsic> {
+        let account = AccountId::from(signer.public());
+        let nonce: Nonce = self
+            .rpc
+            .request("system_accountNextIndex", rpc_params![account])
+            .await?;
+        let genesis_hash: Option<Hash> = self
+            .rpc
+            .request("chain_getBlockHash", rpc_params![0u32])
+            .await?;
+        let version: RuntimeVersion = self
+            .rpc
+            .request("state_getRuntimeVersion", rpc_params![])
+            .await?;
+
+        Ok(signed_extrinsic(
+            signer,
+            call,
+            nonce,
+            genesis_hash.ok_or(Error::MissingGenesis)?,
+            &version,
+        ))
+    }
+
+    /// Submit `extrinsic` to the transaction pool and return its hash.
+    pub async fn submit(&self, extrinsic: UncheckedExtrinsic) -> Result<Hash> {
+        Ok(self
+            .rpc
+            .request(
+                "author_submitExtrinsic",
+                rpc_params![Bytes(extrinsic.encode())],
+            )
+            .await?)
+    }
+
+    /// Sign and submit a `set_username` transaction from `signer`.
+    pub async fn set_username(&self, signer: &sr25519::Pair, username: Vec<u8>) -> Result<Hash> {
+        let call =
+            RuntimeCall::UsernameStorage(pallet_username_storage::Call::set_username { username });
+        let extrinsic = self.sign(signer, call).await?;
+        self.submit(extrinsic).await
+    }
+}
@@ -0,0 +1,103 @@
+//! Command line tool for the username chain.
+
+use clap::{Parser, Subcommand};
+use futures::StreamExt;
+use solochain_template_runtime::AccountId;
+use sp_core::{crypto::Ss58Codec, sr25519, Pair};
+use username_client::{Username, UsernameClient};
+
+#[derive(Debug, Parser)]
+#[command(about = "Read and set usernames on a running node")]
+struct Cli {
+    /// WebSocket endpoint of the node
+    #[arg(long, default_value = "ws://127.0.0.1:9944")]
+    url: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the username of an account
+    Get {
+        #[arg(value_parser = parse_account)]
+        account: AccountId,
+    },
+    /// Set the username of the signing account
+    Set {
+        username: String,
+        /// Secret URI of the signing account
+        #[arg(long, default_value = "//Alice")]
+        suri: String,
+    },
+    /// Print the account owning a username
+    Resolve { username: String },
+    /// Print every account with a username
+    List,
+    /// Print username changes of the given accounts as they happen
+    Watch {
+        #[arg(required = true, value_parser = parse_account)]
+        accounts: Vec<AccountId>,
+        /// Only report changes in finalized blocks
+        #[arg(long)]
+        finalized: bool,
+    },
+}
+
+fn parse_account(s: &str) -> Result<AccountId, String> {
+    AccountId::from_ss58check(s).map_err(|e| format!("invalid account {}: {:?}", s, e))
+}
+
+fn display(username: &Username) -> String {
+    match username {
+        Username::Utf8(name) => name.clone(),
+        Username::Raw { raw } => format!("0x{}", hex::encode(&raw.0)),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    let client = UsernameClient::connect(&cli.url).await?;
+
+    match cli.command {
+        Command::Get { account } => match client.username(account, None).await? {
+            Some(username) => println!("{}", display(&username)),
+            None => println!("<none>"),
+        },
+        Command::Set { username, suri } => {
+            let signer = sr25519::Pair::from_string(&suri, None)
+                .map_err(|e| format!("invalid secret URI: {:?}", e))?;
+            let hash = client.set_username(&signer, username.into_bytes()).await?;
+            println!("{:?}", hash);
+        }
+        Command::Resolve { username } => match client.resolve(username.as_bytes(), None).await? {
+            Some(account) => println!("{}", account),
+            None => println!("<none>"),
+        },
+        Command::List => {
+            for (account, username) in client.all_usernames().await? {
+                println!("{} {}", account, display(&username));
+            }
+        }
+        Command::Watch {
+            accounts,
+            finalized,
+        } => {
+            let mut changes = client.watch(accounts, finalized).await?;
+            while let Some(change) = changes.next().await {
+                let change = change?;
+                let username = change.username.as_ref().map(display);
+                println!(
+                    "{:?} {} {}",
+                    change.block_hash,
+                    change.account,
+                    username.as_deref().unwrap_or("<none>")
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+//! Enforcement of the on-chain node allowlist.
+//!
+//! While the `NodeAllowlist` pallet lists any peer, the node runs its network in reserved-only
+//! mode with the listed peers as the reserved ones, so the connections of every other peer are
+//! refused. The list is followed at each finalized block. An empty list, as on the `dev` and
+//! `local` chains, leaves the network open. Invalid peer ids are ignored, so a list of invalid ids
+//! only keeps the node away from every peer.
+
+use crate::service::FullClient;
+use codec::Decode;
+use futures::StreamExt;
+use pallet_node_allowlist::AllowedNodes;
+use sc_client_api::{BlockchainEvents, StorageProvider};
+use sc_network::{
+	service::traits::{NetworkPeers, NetworkService, NetworkStateInfo},
+	PeerId,
+};
+use solochain_template_runtime::{Hash, Runtime};
+use sp_blockchain::HeaderBackend;
+use sp_core::{hexdisplay::HexDisplay, storage::StorageKey};
+use std::{
+	collections::{BTreeSet, HashSet},
+	sync::Arc,
+};
+
+const LOG_TARGET: &str = "node-allowlist";
+
+/// The peers allowed at block `hash`, or `None` when the network is open.
+pub fn allowed_peers(
+	client: &FullClient,
+	hash: Hash,
+) -> sp_blockchain::Result<Option<HashSet<PeerId>>> {
+	let key = StorageKey(AllowedNodes::<Runtime>::hashed_key().to_vec());
+	let Some(data) = client.storage(hash, &key)? else { return Ok(None) };
+	let nodes = BTreeSet::<Vec<u8>>::decode(&mut &data.0[..])
+		.map_err(|e| sp_blockchain::Error::Application(Box::new(e)))?;
+	if nodes.is_empty() {
+		return Ok(None)
+	}
+
+	let mut peers = HashSet::with_capacity(nodes.len());
+	for node in nodes {
+		match PeerId::from_bytes(&node) {
+			Ok(peer) => {
+				peers.insert(peer);
+			},
+			Err(e) => log::warn!(
+				target: LOG_TARGET,
+				"Ignoring invalid peer id 0x{} in the allowlist: {}",
+				HexDisplay::from(&node),
+				e
+			),
+		}
+	}
+	Ok(Some(peers))
+}
+
+/// Apply the allowlist of every finalized block to `network`.
+pub async fn run(client: Arc<FullClient>, network: Arc<dyn NetworkService>) {
+	let local_peer = network.local_peer_id();
+	let mut finality = client.finality_notification_stream();
+	let mut hash = client.info().finalized_hash;
+	let mut current = None;
+	loop {
+		match allowed_peers(&client, hash) {
+			Ok(allowed) if allowed != current => {
+				match &allowed {
+					Some(peers) => {
+						let mut reserved = peers.clone();
+						reserved.remove(&local_peer);
+						log::info!(
+							target: LOG_TARGET,
+							"Only accepting the {} peers of the allowlist",
+							reserved.len()
+						);
+						network.set_authorized_peers(reserved);
+						network.set_authorized_only(true);
+					},
+					None => {
+						log::info!(target: LOG_TARGET, "Allowlist is empty, accepting all peers");
+						network.set_authorized_only(false);
+						network.set_authorized_peers(HashSet::new());
+					},
+				}
+				current = allowed;
+			},
+			Ok(_) => {},
+			Err(e) =>
+				log::warn!(target: LOG_TARGET, "Unable to read the allowlist at {}: {}", hash, e),
+		}
+
+		match finality.next().await {
+			Some(notification) => hash = notification.hash,
+			None => return,
+		}
+	}
+}
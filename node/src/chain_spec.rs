@@ -1,5 +1,7 @@
 use sc_service::ChainType;
-use solochain_template_runtime::WASM_BINARY;
+use solochain_template_runtime::{
+	genesis_config_presets::PERMISSIONED_RUNTIME_PRESET, WASM_BINARY,
+};
 
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
 pub type ChainSpec = sc_service::GenericChainSpec;
@@ -27,3 +29,17 @@ pub fn local_chain_spec() -> Result<ChainSpec, String> {
 	.with_genesis_config_preset_name(sp_genesis_builder::LOCAL_TESTNET_RUNTIME_PRESET)
 	.build())
 }
+
+/// A local testnet that only accepts the nodes started with the development network keys of Alice
+/// and Bob, see `scripts/run_permissioned_nodes.sh`.
+pub fn permissioned_chain_spec() -> Result<ChainSpec, String> {
+	Ok(ChainSpec::builder(
+		WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?,
+		None,
+	)
+	.with_name("Permissioned Testnet")
+	.with_id("permissioned_testnet")
+	.with_chain_type(ChainType::Local)
+	.with_genesis_config_preset_name(PERMISSIONED_RUNTIME_PRESET)
+	.build())
+}
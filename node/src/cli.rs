@@ -7,6 +7,33 @@ pub struct Cli {
 
 	#[clap(flatten)]
 	pub run: RunCmd,
+
+	#[clap(flatten)]
+	pub username: UsernameParams,
+
+	/// Author blocks with manual sealing instead of Aura and GRANDPA, for development and tests.
+	///
+	/// `instant` seals a block for every new transaction, `manual` only when `engine_createBlock`
+	/// is called, and `interval=<ms>` every `<ms>` milliseconds. The `engine_createBlock` and
+	/// `engine_finalizeBlock` RPCs are available in every mode.
+	#[arg(long, value_name = "MODE")]
+	pub sealing: Option<crate::sealing::Sealing>,
+}
+
+/// Username services of the node.
+#[derive(Debug, Clone, clap::Args)]
+pub struct UsernameParams {
+	/// Serve the username HTTP gateway on this address, for example `127.0.0.1:8080`.
+	#[arg(long)]
+	pub username_gateway: Option<std::net::SocketAddr>,
+
+	/// Index username events in a local database and serve them through
+	/// `usernameStorage_events`.
+	///
+	/// Events are read from the state of each block, so the full history is only indexed when the
+	/// node keeps the state of every block, see `--state-pruning archive`.
+	#[arg(long)]
+	pub username_indexer: bool,
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -43,4 +70,10 @@ pub enum Subcommand {
 
 	/// Db meta columns information.
 	ChainInfo(sc_cli::ChainInfoCmd),
+
+	/// Export the usernames stored at a block as JSON or CSV.
+	ExportUsernames(crate::usernames::ExportUsernamesCmd),
+
+	/// Turn a JSON or CSV username list into a genesis patch for `build-spec`.
+	ImportUsernames(crate::usernames::ImportUsernamesCmd),
 }
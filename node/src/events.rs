@@ -0,0 +1,125 @@
+//! Access to the events deposited in a block.
+
+use crate::service::FullClient;
+use codec::{Decode, Encode};
+use pallet_username_storage::{BalanceOf, Config, Event};
+use sc_client_api::StorageProvider;
+use solochain_template_runtime::{AccountId, Balance, BlockNumber, Hash, Runtime, RuntimeEvent};
+use sp_core::storage::StorageKey;
+
+pub type EventRecord = frame_system::EventRecord<RuntimeEvent, Hash>;
+
+/// The events deposited in block `hash`, read from its state.
+pub fn events_at(client: &FullClient, hash: Hash) -> sp_blockchain::Result<Vec<EventRecord>> {
+	let key = StorageKey(frame_system::Events::<Runtime>::hashed_key().to_vec());
+	let Some(data) = client.storage(hash, &key)? else { return Ok(Vec::new()) };
+	Vec::<EventRecord>::decode(&mut &data.0[..])
+		.map_err(|e| sp_blockchain::Error::Application(Box::new(e)))
+}
+
+/// A username event of any registry.
+///
+/// Every registry is an instance of the same pallet, with its own `MaxUsernameLength`, so their
+/// events are different types. This is the form they share, with usernames as plain bytes.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum UsernameEvent {
+	UsernameSet { who: AccountId, username: Vec<u8> },
+	UsernameUpdated { who: AccountId, username: Vec<u8> },
+	UsernameRetrieved { who: AccountId, target: AccountId, username: Vec<u8> },
+	UsernameCleared { who: AccountId },
+	RegistrationSponsored { who: AccountId },
+	UsernameTransferred { from: AccountId, to: AccountId, username: Vec<u8> },
+	UsernameListed { who: AccountId, username: Vec<u8>, price: Balance },
+	UsernameDelisted { who: AccountId, username: Vec<u8> },
+	UsernameSold { seller: AccountId, buyer: AccountId, username: Vec<u8>, price: Balance },
+	UsernameForceCleared { who: AccountId, username: Vec<u8> },
+	UsernameBlocked { username: Vec<u8> },
+	UsernameUnblocked { username: Vec<u8> },
+	UsernameChangeScheduled { who: AccountId, username: Vec<u8>, at: BlockNumber },
+	UsernameChangeCancelled { who: AccountId, username: Vec<u8> },
+}
+
+impl<I: 'static> TryFrom<Event<Runtime, I>> for UsernameEvent
+where
+	Runtime: Config<I>,
+	BalanceOf<Runtime, I>: Into<Balance>,
+{
+	type Error = ();
+
+	fn try_from(event: Event<Runtime, I>) -> Result<Self, ()> {
+		Ok(match event {
+			Event::UsernameSet { who, username } =>
+				Self::UsernameSet { who, username: username.into_inner() },
+			Event::UsernameUpdated { who, username } =>
+				Self::UsernameUpdated { who, username: username.into_inner() },
+			Event::UsernameRetrieved { who, target, username } =>
+				Self::UsernameRetrieved { who, target, username: username.into_inner() },
+			Event::UsernameCleared { who } => Self::UsernameCleared { who },
+			Event::RegistrationSponsored { who } => Self::RegistrationSponsored { who },
+			Event::UsernameTransferred { from, to, username } =>
+				Self::UsernameTransferred { from, to, username: username.into_inner() },
+			Event::UsernameListed { who, username, price } =>
+				Self::UsernameListed { who, username: username.into_inner(), price: price.into() },
+			Event::UsernameDelisted { who, username } =>
+				Self::UsernameDelisted { who, username: username.into_inner() },
+			Event::UsernameSold { seller, buyer, username, price } => Self::UsernameSold {
+				seller,
+				buyer,
+				username: username.into_inner(),
+				price: price.into(),
+			},
+			Event::UsernameForceCleared { who, username } =>
+				Self::UsernameForceCleared { who, username: username.into_inner() },
+			Event::UsernameBlocked { username } =>
+				Self::UsernameBlocked { username: username.into_inner() },
+			Event::UsernameUnblocked { username } =>
+				Self::UsernameUnblocked { username: username.into_inner() },
+			Event::UsernameChangeScheduled { who, username, at } =>
+				Self::UsernameChangeScheduled { who, username: username.into_inner(), at },
+			Event::UsernameChangeCancelled { who, username } =>
+				Self::UsernameChangeCancelled { who, username: username.into_inner() },
+			_ => return Err(()),
+		})
+	}
+}
+
+/// The username event carried by `event`, with the name of its registry, as listed by
+/// `usernameStorage_registries`.
+pub fn username_event(event: RuntimeEvent) -> Option<(&'static str, UsernameEvent)> {
+	match event {
+		RuntimeEvent::UsernameStorage(event) => Some(("users", event.try_into().ok()?)),
+		RuntimeEvent::BotUsernames(event) => Some(("bots", event.try_into().ok()?)),
+		RuntimeEvent::OrganizationUsernames(event) =>
+			Some(("organizations", event.try_into().ok()?)),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::BoundedVec;
+
+	#[test]
+	fn long_organization_username_event() {
+		// Longer than the 32 bytes allowed by the default registry.
+		let username = b"a-very-long-organization-name-of-40-byte".to_vec();
+		assert_eq!(username.len(), 40);
+		let who = AccountId::from([1; 32]);
+		let event = RuntimeEvent::OrganizationUsernames(Event::UsernameSet {
+			who: who.clone(),
+			username: BoundedVec::try_from(username.clone()).unwrap(),
+		});
+
+		assert_eq!(
+			username_event(event),
+			Some(("organizations", UsernameEvent::UsernameSet { who, username }))
+		);
+	}
+
+	#[test]
+	fn other_events_are_ignored() {
+		let event = RuntimeEvent::System(frame_system::Event::CodeUpdated);
+		assert_eq!(username_event(event), None);
+	}
+}
@@ -0,0 +1,443 @@
+//! HTTP gateway for username reads and writes.
+//!
+//! Serves a small JSON API for consumers that cannot speak JSON-RPC. Reads go through
+//! `UsernameStorageApi` at the best block and writes are handed to the transaction pool, so the
+//! gateway keeps no state of its own. Reads take an optional `registry` query parameter naming the
+//! username registry to read, the default registry when not given. The API is described by the
+//! OpenAPI document served at `GET /openapi.json`.
+
+use codec::DecodeAll;
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::{
+	body::{Bytes, Incoming},
+	header,
+	server::conn::http1,
+	service::service_fn,
+	Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use pallet_username_storage::UsernameStorageApi;
+use percent_encoding::percent_decode_str;
+use sc_service::SpawnTaskHandle;
+use sc_transaction_pool_api::{TransactionPool, TransactionSource};
+use serde_json::{json, Value};
+use solochain_template_runtime::{opaque::Block, AccountId, RuntimeCall, UncheckedExtrinsic};
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_core::crypto::Ss58Codec;
+use sp_runtime::traits::Block as BlockT;
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+use username_storage_rpc::Username;
+
+const LOG_TARGET: &str = "username-gateway";
+
+/// OpenAPI description of the gateway.
+const OPENAPI: &str = include_str!("openapi.json");
+
+/// Number of usernames returned by `GET /usernames` when no `limit` is given.
+const DEFAULT_PAGE_SIZE: u32 = 100;
+/// Largest `limit` accepted by `GET /usernames`.
+const MAX_PAGE_SIZE: u32 = 1000;
+/// Largest request body accepted by `POST /usernames`.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Version of `UsernameStorageApi` needed for reverse lookups and listing.
+const LISTING_API_VERSION: u32 = 3;
+/// Version of `UsernameStorageApi` needed for lookups in a named registry.
+const REGISTRY_API_VERSION: u32 = 6;
+/// Version of `UsernameStorageApi` needed for listing a named registry.
+const REGISTRY_LISTING_API_VERSION: u32 = 7;
+
+type Body = Full<Bytes>;
+
+/// Bind the gateway to `addr` and serve it until the node shuts down.
+pub fn spawn<C, P>(
+	spawn_handle: SpawnTaskHandle,
+	addr: SocketAddr,
+	client: Arc<C>,
+	pool: Arc<P>,
+) -> std::io::Result<()>
+where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: UsernameStorageApi<Block, AccountId>,
+	P: TransactionPool<Block = Block> + 'static,
+{
+	// Bind right away so that a busy address stops the node from starting.
+	let listener = std::net::TcpListener::bind(addr)?;
+	listener.set_nonblocking(true)?;
+	log::info!(target: LOG_TARGET, "Username gateway listening on http://{}", addr);
+
+	let gateway = Gateway { client, pool };
+	spawn_handle.clone().spawn("username-gateway", Some("gateway"), async move {
+		let listener = match tokio::net::TcpListener::from_std(listener) {
+			Ok(listener) => listener,
+			Err(e) => {
+				log::error!(target: LOG_TARGET, "Unable to start username gateway: {}", e);
+				return
+			},
+		};
+
+		loop {
+			let stream = match listener.accept().await {
+				Ok((stream, _)) => stream,
+				Err(e) => {
+					log::debug!(target: LOG_TARGET, "Failed to accept connection: {}", e);
+					continue
+				},
+			};
+
+			let gateway = gateway.clone();
+			spawn_handle.spawn("username-gateway-connection", Some("gateway"), async move {
+				let service = service_fn(move |req| {
+					let gateway = gateway.clone();
+					async move { Ok::<_, Infallible>(gateway.handle(req).await) }
+				});
+				if let Err(e) =
+					http1::Builder::new().serve_connection(TokioIo::new(stream), service).await
+				{
+					log::debug!(target: LOG_TARGET, "Connection error: {}", e);
+				}
+			});
+		}
+	});
+
+	Ok(())
+}
+
+fn json_response(status: StatusCode, value: Value) -> Response<Body> {
+	Response::builder()
+		.status(status)
+		.header(header::CONTENT_TYPE, "application/json")
+		.body(Full::new(Bytes::from(value.to_string())))
+		.expect("Status and headers are valid; qed")
+}
+
+fn error(status: StatusCode, message: impl ToString) -> Response<Body> {
+	json_response(status, json!({ "error": message.to_string() }))
+}
+
+fn parse_account(s: &str) -> Result<AccountId, Response<Body>> {
+	AccountId::from_ss58check(s)
+		.map_err(|_| error(StatusCode::BAD_REQUEST, format!("Invalid account: {}", s)))
+}
+
+/// Key and value pairs of a query string, with the values percent decoded.
+fn query_pairs(query: &str) -> impl Iterator<Item = (&str, String)> {
+	query
+		.split('&')
+		.filter_map(|pair| pair.split_once('='))
+		.map(|(key, value)| (key, percent_decode_str(value).decode_utf8_lossy().into_owned()))
+}
+
+/// The `registry` query parameter, `None` for the default registry.
+fn query_registry(query: &str) -> Option<String> {
+	query_pairs(query).find(|(key, value)| *key == "registry" && !value.is_empty()).map(|(_, v)| v)
+}
+
+/// The `cursor` and `limit` query parameters of `GET /usernames`.
+fn page_params(query: &str) -> Result<(Option<AccountId>, u32), Response<Body>> {
+	let mut cursor = None;
+	let mut limit = DEFAULT_PAGE_SIZE;
+	for (key, value) in query_pairs(query) {
+		match key {
+			"cursor" if !value.is_empty() => cursor = Some(parse_account(&value)?),
+			"limit" =>
+				limit = value
+					.parse::<u32>()
+					.ok()
+					.filter(|limit| (1..=MAX_PAGE_SIZE).contains(limit))
+					.ok_or_else(|| {
+						error(
+							StatusCode::BAD_REQUEST,
+							format!("limit must be between 1 and {}", MAX_PAGE_SIZE),
+						)
+					})?,
+			_ => {},
+		}
+	}
+	Ok((cursor, limit))
+}
+
+/// The cursor of the page following `page`, `None` when it is the last one.
+fn next_cursor(page: &[(AccountId, Vec<u8>)], limit: u32) -> Option<String> {
+	// A full page may be followed by more entries, the last account is the next cursor.
+	(page.len() == limit as usize)
+		.then(|| page.last().map(|(account, _)| account.to_ss58check()))
+		.flatten()
+}
+
+/// Only calls to a username registry are accepted, the gateway is not a general purpose relay.
+fn is_username_call(call: &RuntimeCall) -> bool {
+	matches!(
+		call,
+		RuntimeCall::UsernameStorage(_) |
+			RuntimeCall::BotUsernames(_) |
+			RuntimeCall::OrganizationUsernames(_)
+	)
+}
+
+fn unknown_registry(registry: &str) -> Response<Body> {
+	error(StatusCode::NOT_FOUND, format!("Unknown registry: {}", registry))
+}
+
+fn username_entry(account: &AccountId, username: Vec<u8>) -> Value {
+	json!({ "account": account.to_ss58check(), "username": Username::from(username) })
+}
+
+struct Gateway<C, P> {
+	client: Arc<C>,
+	pool: Arc<P>,
+}
+
+impl<C, P> Clone for Gateway<C, P> {
+	fn clone(&self) -> Self {
+		Self { client: self.client.clone(), pool: self.pool.clone() }
+	}
+}
+
+impl<C, P> Gateway<C, P>
+where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: UsernameStorageApi<Block, AccountId>,
+	P: TransactionPool<Block = Block> + 'static,
+{
+	async fn handle(self, req: Request<Incoming>) -> Response<Body> {
+		let path = req.uri().path().to_owned();
+		let query = req.uri().query().unwrap_or_default().to_owned();
+		let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+		let result = match (req.method(), segments.as_slice()) {
+			(&Method::GET, ["openapi.json"]) => Ok(Response::builder()
+				.header(header::CONTENT_TYPE, "application/json")
+				.body(Full::new(Bytes::from_static(OPENAPI.as_bytes())))
+				.expect("Headers are valid; qed")),
+			(&Method::GET, ["usernames", account]) => self.username(account, &query),
+			(&Method::GET, ["accounts", username]) => self.account(username, &query),
+			(&Method::GET, ["usernames"]) => self.list(&query),
+			(&Method::POST, ["usernames"]) => self.submit(req).await,
+			_ => Err(error(StatusCode::NOT_FOUND, "Not found")),
+		};
+
+		result.unwrap_or_else(|response| response)
+	}
+
+	/// Best block hash, after checking the runtime implements `version` of the API.
+	fn best_hash(&self, version: u32) -> Result<<Block as BlockT>::Hash, Response<Body>> {
+		let at = self.client.info().best_hash;
+		let found = self
+			.client
+			.runtime_api()
+			.api_version::<dyn UsernameStorageApi<Block, AccountId>>(at)
+			.map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+		match found {
+			Some(found) if found >= version => Ok(at),
+			_ => Err(error(
+				StatusCode::NOT_IMPLEMENTED,
+				format!("Runtime does not implement version {} of UsernameStorageApi", version),
+			)),
+		}
+	}
+
+	/// `GET /usernames/{account}?registry=`
+	fn username(&self, account: &str, query: &str) -> Result<Response<Body>, Response<Body>> {
+		let account = parse_account(account)?;
+		let username = match query_registry(query) {
+			Some(registry) => {
+				let at = self.best_hash(REGISTRY_API_VERSION)?;
+				self.client
+					.runtime_api()
+					.registry_usernames(at, registry.clone().into_bytes(), vec![account.clone()])
+					.map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+					.ok_or_else(|| unknown_registry(&registry))?
+					.pop()
+					.flatten()
+			},
+			None => {
+				let at = self.best_hash(1)?;
+				self.client
+					.runtime_api()
+					.get_username(at, account.clone())
+					.map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+			},
+		}
+		.ok_or_else(|| error(StatusCode::NOT_FOUND, "Account has no username"))?;
+
+		Ok(json_response(StatusCode::OK, username_entry(&account, username)))
+	}
+
+	/// `GET /accounts/{username}?registry=`
+	fn account(&self, username: &str, query: &str) -> Result<Response<Body>, Response<Body>> {
+		let username: Vec<u8> = percent_decode_str(username).collect();
+		let account = match query_registry(query) {
+			Some(registry) => {
+				let at = self.best_hash(REGISTRY_API_VERSION)?;
+				self.client
+					.runtime_api()
+					.registry_account(at, registry.clone().into_bytes(), username.clone())
+					.map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+					.ok_or_else(|| unknown_registry(&registry))?
+			},
+			None => {
+				let at = self.best_hash(LISTING_API_VERSION)?;
+				self.client
+					.runtime_api()
+					.get_account(at, username.clone())
+					.map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+			},
+		}
+		.ok_or_else(|| error(StatusCode::NOT_FOUND, "Username is not registered"))?;
+
+		Ok(json_response(StatusCode::OK, username_entry(&account, username)))
+	}
+
+	/// `GET /usernames?cursor=&limit=&registry=`
+	fn list(&self, query: &str) -> Result<Response<Body>, Response<Body>> {
+		let (cursor, limit) = page_params(query)?;
+		let page = match query_registry(query) {
+			Some(registry) => {
+				let at = self.best_hash(REGISTRY_LISTING_API_VERSION)?;
+				self.client
+					.runtime_api()
+					.registry_usernames_page(at, registry.clone().into_bytes(), cursor, limit)
+					.map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+					.ok_or_else(|| unknown_registry(&registry))?
+			},
+			None => {
+				let at = self.best_hash(LISTING_API_VERSION)?;
+				self.client
+					.runtime_api()
+					.usernames_page(at, cursor, limit)
+					.map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e))?
+			},
+		};
+
+		let next = next_cursor(&page, limit);
+		let usernames: Vec<Value> = page
+			.into_iter()
+			.map(|(account, username)| username_entry(&account, username))
+			.collect();
+
+		Ok(json_response(StatusCode::OK, json!({ "usernames": usernames, "next": next })))
+	}
+
+	/// `POST /usernames` with `{ "extrinsic": "0x..." }`
+	async fn submit(&self, req: Request<Incoming>) -> Result<Response<Body>, Response<Body>> {
+		let body = Limited::new(req.into_body(), MAX_BODY_SIZE)
+			.collect()
+			.await
+			.map_err(|_| error(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large"))?
+			.to_bytes();
+		let body: Value = serde_json::from_slice(&body)
+			.map_err(|e| error(StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)))?;
+		let bytes = body["extrinsic"]
+			.as_str()
+			.and_then(|hex| sp_core::bytes::from_hex(hex).ok())
+			.ok_or_else(|| {
+			error(StatusCode::BAD_REQUEST, "Expected a hex encoded `extrinsic`")
+		})?;
+
+		let extrinsic = UncheckedExtrinsic::decode_all(&mut &bytes[..])
+			.map_err(|e| error(StatusCode::BAD_REQUEST, format!("Invalid extrinsic: {}", e)))?;
+		if !is_username_call(&extrinsic.function) {
+			return Err(error(StatusCode::BAD_REQUEST, "Not a username storage call"));
+		}
+		let opaque = <Block as BlockT>::Extrinsic::decode_all(&mut &bytes[..])
+			.map_err(|e| error(StatusCode::BAD_REQUEST, format!("Invalid extrinsic: {}", e)))?;
+
+		let at = self.client.info().best_hash;
+		let hash = self
+			.pool
+			.submit_one(at, TransactionSource::External, opaque)
+			.await
+			.map_err(|e| error(StatusCode::UNPROCESSABLE_ENTITY, e))?;
+
+		Ok(json_response(StatusCode::ACCEPTED, json!({ "hash": hash })))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use frame_support::BoundedVec;
+	use solochain_template_runtime::{Runtime, UsernameStorage};
+
+	fn account(n: u8) -> AccountId {
+		AccountId::from([n; 32])
+	}
+
+	#[test]
+	fn query_values_are_decoded() {
+		let pairs: Vec<_> = query_pairs("registry=bot%20names&flag&limit=10").collect();
+		assert_eq!(pairs, vec![("registry", "bot names".to_owned()), ("limit", "10".to_owned())]);
+
+		assert_eq!(query_registry("limit=10&registry=bots"), Some("bots".to_owned()));
+		assert_eq!(query_registry("registry="), None);
+		assert_eq!(query_registry(""), None);
+	}
+
+	#[test]
+	fn accounts_are_ss58() {
+		assert_eq!(parse_account(&account(1).to_ss58check()).unwrap(), account(1));
+		assert_eq!(parse_account("alice").unwrap_err().status(), StatusCode::BAD_REQUEST);
+	}
+
+	#[test]
+	fn page_params_are_checked() {
+		assert_eq!(page_params("").unwrap(), (None, DEFAULT_PAGE_SIZE));
+		let query = format!("cursor={}&limit=2", account(1).to_ss58check());
+		assert_eq!(page_params(&query).unwrap(), (Some(account(1)), 2));
+		assert_eq!(page_params("cursor=&limit=1000").unwrap(), (None, MAX_PAGE_SIZE));
+
+		for query in ["limit=0", "limit=1001", "limit=ten", "cursor=alice"] {
+			assert_eq!(page_params(query).unwrap_err().status(), StatusCode::BAD_REQUEST);
+		}
+	}
+
+	#[test]
+	fn paging_walks_every_username() {
+		sp_io::TestExternalities::default().execute_with(|| {
+			for n in 1..=4 {
+				let username = BoundedVec::truncate_from(vec![b'a' + n; 5]);
+				pallet_username_storage::Usernames::<Runtime>::insert(account(n), username);
+			}
+
+			let mut cursor = None;
+			let mut pages = Vec::new();
+			loop {
+				let page = UsernameStorage::usernames_page(cursor, 2);
+				pages.push(page.len());
+				let next = next_cursor(&page, 2);
+				cursor = next.map(|next| parse_account(&next).unwrap());
+				if cursor.is_none() {
+					break;
+				}
+			}
+			// The last full page is followed by an empty one.
+			assert_eq!(pages, vec![2, 2, 0]);
+		});
+	}
+
+	#[test]
+	fn last_page_has_no_cursor() {
+		let page = vec![(account(1), b"alice".to_vec()), (account(2), b"bobby".to_vec())];
+		assert_eq!(next_cursor(&page, 2), Some(account(2).to_ss58check()));
+		assert_eq!(next_cursor(&page, 3), None);
+		assert_eq!(next_cursor(&[], 2), None);
+	}
+
+	#[test]
+	fn only_username_calls_are_relayed() {
+		use pallet_username_storage::Call;
+
+		let username = b"alice".to_vec();
+		assert!(is_username_call(&RuntimeCall::UsernameStorage(Call::set_username {
+			username: username.clone(),
+		})));
+		assert!(is_username_call(&RuntimeCall::BotUsernames(Call::set_username { username })));
+		assert!(is_username_call(&RuntimeCall::OrganizationUsernames(Call::clear_username {})));
+		assert!(!is_username_call(&RuntimeCall::System(frame_system::Call::remark {
+			remark: b"alice".to_vec(),
+		})));
+	}
+}
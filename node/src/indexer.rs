@@ -0,0 +1,561 @@
+//! History of username events, served by `usernameStorage_events`.
+//!
+//! The indexer follows the chain and stores every `pallet_username_storage` event, of every
+//! username registry, under each account it involves, in a parity-db database next to the chain
+//! database. Only finalized blocks are written, and the checkpoint of the last indexed block is
+//! written in the same commit, so a restarted node resumes right after it. Events of blocks that
+//! are not finalized yet are kept in memory and only returned while their block is on the best
+//! chain, so re-orgs never reach the database.
+//!
+//! Events are read from the state of each block. Blocks whose state was pruned cannot be read, so
+//! indexing starts at the oldest finalized block that still has its state, and jumps over pruned
+//! blocks if the node was stopped for longer than the pruning window. Any other failure to read a
+//! block stops indexing before it, until the next finalized block. Run the node with
+//! `--state-pruning archive` to index the full history.
+
+use crate::{
+	events::{events_at, username_event, UsernameEvent},
+	service::FullClient,
+};
+use codec::{Decode, Encode};
+use futures::{stream, StreamExt};
+use jsonrpsee::{core::RpcResult, proc_macros::rpc};
+use parking_lot::Mutex;
+use sc_client_api::BlockchainEvents;
+use serde_json::{json, Value};
+use solochain_template_runtime::{AccountId, BlockNumber, Hash};
+use sp_blockchain::HeaderBackend;
+use sp_core::crypto::Ss58Codec;
+use std::{collections::HashMap, fmt, path::Path, sync::Arc};
+use username_storage_rpc::{Error as RpcError, Username};
+
+const LOG_TARGET: &str = "username-indexer";
+
+/// Column holding the checkpoint.
+const META_COLUMN: u8 = 0;
+/// Column holding the events, keyed by account, block number and event index.
+const EVENTS_COLUMN: u8 = 1;
+/// Key of the last indexed block in [`META_COLUMN`].
+const CHECKPOINT_KEY: &[u8] = b"checkpoint";
+/// Key of the format of the index in [`META_COLUMN`].
+const VERSION_KEY: &[u8] = b"version";
+/// Format of the index, an index in another format is rebuilt.
+const VERSION: u32 = 2;
+/// Number of finalized blocks written per commit while catching up.
+const COMMIT_BLOCKS: BlockNumber = 256;
+/// Most blocks above the checkpoint read from the chain to answer a query.
+const MAX_WALK_BLOCKS: BlockNumber = 256;
+
+/// Errors of the indexer.
+#[derive(Debug)]
+pub enum Error {
+	/// The index database failed
+	Db(parity_db::Error),
+	/// The chain could not be read
+	Client(sp_blockchain::Error),
+	/// The index holds data that does not decode
+	Codec(codec::Error),
+	/// The index is too far behind the chain to answer, blocks up to the given one are indexed
+	CatchingUp(BlockNumber),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Db(e) => write!(f, "Index database error: {}", e),
+			Self::Client(e) => write!(f, "Unable to read the chain: {}", e),
+			Self::Codec(e) => write!(f, "Unable to decode the index: {}", e),
+			Self::CatchingUp(number) =>
+				write!(f, "The index is catching up with the chain, at block #{}", number),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<parity_db::Error> for Error {
+	fn from(e: parity_db::Error) -> Self {
+		Self::Db(e)
+	}
+}
+
+impl From<sp_blockchain::Error> for Error {
+	fn from(e: sp_blockchain::Error) -> Self {
+		Self::Client(e)
+	}
+}
+
+impl From<codec::Error> for Error {
+	fn from(e: codec::Error) -> Self {
+		Self::Codec(e)
+	}
+}
+
+/// A username event along with where it was deposited.
+#[derive(Clone, Encode, Decode)]
+pub struct IndexedEvent {
+	pub block_number: BlockNumber,
+	pub block_hash: Hash,
+	pub event_index: u32,
+	/// Name of the username registry the event belongs to
+	pub registry: String,
+	pub event: UsernameEvent,
+}
+
+/// The accounts involved in `event`.
+fn accounts(event: &UsernameEvent) -> Vec<&AccountId> {
+	use UsernameEvent::*;
+
+	match event {
+		UsernameSet { who, .. } |
+		UsernameUpdated { who, .. } |
+		UsernameCleared { who } |
+		UsernameForceCleared { who, .. } |
+		RegistrationSponsored { who } |
+		UsernameListed { who, .. } |
+		UsernameDelisted { who, .. } |
+		UsernameChangeScheduled { who, .. } |
+		UsernameChangeCancelled { who, .. } => vec![who],
+		UsernameRetrieved { who, target, .. } => vec![who, target],
+		UsernameTransferred { from, to, .. } => vec![from, to],
+		UsernameSold { seller, buyer, .. } => vec![seller, buyer],
+		_ => Vec::new(),
+	}
+}
+
+/// Key of an event in [`EVENTS_COLUMN`], ordered by account, then by position in the chain.
+fn event_key(account: &AccountId, number: BlockNumber, index: u32) -> Vec<u8> {
+	let mut key = account.encode();
+	key.extend(number.to_be_bytes());
+	key.extend(index.to_be_bytes());
+	key
+}
+
+/// The username events of block `hash`.
+fn block_events(
+	client: &FullClient,
+	hash: Hash,
+	number: BlockNumber,
+) -> sp_blockchain::Result<Vec<IndexedEvent>> {
+	Ok(events_at(client, hash)?
+		.into_iter()
+		.enumerate()
+		.filter_map(|(index, record)| {
+			let (registry, event) = username_event(record.event)?;
+			Some(IndexedEvent {
+				block_number: number,
+				block_hash: hash,
+				event_index: index as u32,
+				registry: registry.into(),
+				event,
+			})
+		})
+		.collect())
+}
+
+/// The first block to index after `checkpoint`, given the hash of the finalized block at its
+/// height, or `None` when that block is not the indexed one and the index must be rebuilt.
+fn resume_at(
+	checkpoint: Option<(BlockNumber, Hash)>,
+	finalized_hash: Option<Hash>,
+) -> Option<BlockNumber> {
+	match checkpoint {
+		Some((number, hash)) if finalized_hash == Some(hash) => Some(number + 1),
+		Some(_) => None,
+		None => Some(1),
+	}
+}
+
+/// The first block from `from` to `to` for which `has_state` holds, given that it holds for every
+/// block above it, or `to + 1` if there is none.
+fn oldest_with_state(
+	from: BlockNumber,
+	to: BlockNumber,
+	has_state: impl Fn(BlockNumber) -> bool,
+) -> BlockNumber {
+	let (mut low, mut high) = (from, to.saturating_add(1));
+	while low < high {
+		let middle = low + (high - low) / 2;
+		if has_state(middle) {
+			high = middle;
+		} else {
+			low = middle + 1;
+		}
+	}
+	low
+}
+
+/// The block below the ones a query from block `from` must read from the chain, with blocks up to
+/// `indexed` in the database.
+fn walk_floor(indexed: BlockNumber, from: BlockNumber) -> BlockNumber {
+	indexed.max(from.saturating_sub(1))
+}
+
+/// Username event index of the node.
+pub struct Indexer {
+	db: parity_db::Db,
+	client: Arc<FullClient>,
+	/// Events of imported blocks above the last finalized block, by block hash.
+	unfinalized: Mutex<HashMap<Hash, (BlockNumber, Vec<IndexedEvent>)>>,
+}
+
+impl Indexer {
+	/// Open the index stored at `path`, creating it if needed.
+	pub fn open(path: &Path, client: Arc<FullClient>) -> Result<Self, Error> {
+		let mut options = parity_db::Options::with_columns(path, 2);
+		options.columns[EVENTS_COLUMN as usize].btree_index = true;
+		let db = parity_db::Db::open_or_create(&options)?;
+		let indexer = Self { db, client, unfinalized: Default::default() };
+
+		let version = indexer
+			.db
+			.get(META_COLUMN, VERSION_KEY)?
+			.map(|value| u32::decode(&mut &value[..]))
+			.transpose()?;
+		if version != Some(VERSION) {
+			log::info!(target: LOG_TARGET, "Index format changed, rebuilding the index");
+			indexer.clear()?;
+		}
+		Ok(indexer)
+	}
+
+	/// The last indexed block.
+	fn checkpoint(&self) -> Result<Option<(BlockNumber, Hash)>, Error> {
+		Ok(self
+			.db
+			.get(META_COLUMN, CHECKPOINT_KEY)?
+			.map(|value| Decode::decode(&mut &value[..]))
+			.transpose()?)
+	}
+
+	/// Remove everything from the index.
+	fn clear(&self) -> Result<(), Error> {
+		let mut changes = vec![
+			(META_COLUMN, CHECKPOINT_KEY.to_vec(), None),
+			(META_COLUMN, VERSION_KEY.to_vec(), Some(VERSION.encode())),
+		];
+		let mut iter = self.db.iter(EVENTS_COLUMN)?;
+		iter.seek_to_first()?;
+		while let Some((key, _)) = iter.next()? {
+			changes.push((EVENTS_COLUMN, key, None));
+		}
+		Ok(self.db.commit(changes)?)
+	}
+
+	/// Write the finalized blocks that are not indexed yet.
+	fn index_finalized(&self) -> Result<(), Error> {
+		let finalized = self.client.info().finalized_number;
+		let checkpoint = self.checkpoint()?;
+		let finalized_hash = match checkpoint {
+			Some((number, _)) => self.client.hash(number)?,
+			None => None,
+		};
+		let mut next = match resume_at(checkpoint, finalized_hash) {
+			Some(next) => next,
+			None => {
+				log::warn!(
+					target: LOG_TARGET,
+					"Indexed block #{} was reverted, rebuilding the index",
+					checkpoint.map_or(0, |(number, _)| number),
+				);
+				self.clear()?;
+				1
+			},
+		};
+
+		// The state of finalized blocks is pruned from the oldest, so only those from the oldest
+		// one still having its state can be read.
+		let oldest = oldest_with_state(next, finalized, |number| {
+			self.client
+				.hash(number)
+				.ok()
+				.flatten()
+				.is_some_and(|hash| self.client.state_at(hash).is_ok())
+		});
+		if oldest > next {
+			log::warn!(
+				target: LOG_TARGET,
+				"State of blocks #{} to #{} is pruned, they are not indexed",
+				next,
+				oldest - 1,
+			);
+			next = oldest;
+		}
+
+		while next <= finalized {
+			let last = finalized.min(next.saturating_add(COMMIT_BLOCKS - 1));
+			let mut changes = Vec::new();
+			let mut checkpoint = None;
+			for number in next..=last {
+				let hash = self.client.hash(number)?.ok_or_else(|| {
+					sp_blockchain::Error::UnknownBlock(format!("finalized block #{}", number))
+				})?;
+				let events = match block_events(&self.client, hash, number) {
+					Ok(events) => events,
+					Err(e) => {
+						// Keep the blocks read so far, the next attempt resumes at this one.
+						self.commit(changes, checkpoint)?;
+						log::warn!(
+							target: LOG_TARGET,
+							"Unable to index block #{}, retrying with the next finalized block",
+							number,
+						);
+						return Err(e.into())
+					},
+				};
+				for event in events {
+					for account in accounts(&event.event) {
+						changes.push((
+							EVENTS_COLUMN,
+							event_key(account, number, event.event_index),
+							Some(event.encode()),
+						));
+					}
+				}
+				checkpoint = Some((number, hash));
+			}
+			self.commit(changes, checkpoint)?;
+			log::debug!(target: LOG_TARGET, "Indexed blocks #{} to #{}", next, last);
+			next = last + 1;
+		}
+
+		self.unfinalized.lock().retain(|_, (number, _)| *number > finalized);
+		Ok(())
+	}
+
+	/// Write `changes` along with the new `checkpoint`, if any block was read.
+	fn commit(
+		&self,
+		mut changes: Vec<(u8, Vec<u8>, Option<Vec<u8>>)>,
+		checkpoint: Option<(BlockNumber, Hash)>,
+	) -> Result<(), Error> {
+		let Some(checkpoint) = checkpoint else { return Ok(()) };
+		changes.push((META_COLUMN, CHECKPOINT_KEY.to_vec(), Some(checkpoint.encode())));
+		Ok(self.db.commit(changes)?)
+	}
+
+	/// Keep the events of an imported block until it is finalized.
+	fn import(&self, hash: Hash, number: BlockNumber) {
+		if number <= self.client.info().finalized_number {
+			return
+		}
+		match block_events(&self.client, hash, number) {
+			Ok(events) => {
+				self.unfinalized.lock().insert(hash, (number, events));
+			},
+			Err(e) => log::debug!(target: LOG_TARGET, "Unable to read events of {:?}: {}", hash, e),
+		}
+	}
+
+	/// Username events involving `account` from block `from` to block `to`, both included, in
+	/// chain order.
+	pub fn events(
+		&self,
+		account: &AccountId,
+		from: BlockNumber,
+		to: BlockNumber,
+	) -> Result<Vec<IndexedEvent>, Error> {
+		let mut found = Vec::new();
+		if from > to {
+			return Ok(found)
+		}
+		let indexed = self.checkpoint()?.map_or(0, |(number, _)| number);
+		let info = self.client.info();
+		let floor = walk_floor(indexed, from);
+		if to > floor && info.best_number.saturating_sub(floor) > MAX_WALK_BLOCKS {
+			return Err(Error::CatchingUp(indexed))
+		}
+
+		// Finalized history, from the database.
+		let prefix = account.encode();
+		let mut iter = self.db.iter(EVENTS_COLUMN)?;
+		iter.seek(&event_key(account, from, 0))?;
+		while let Some((key, value)) = iter.next()? {
+			if !key.starts_with(&prefix) {
+				break
+			}
+			let event = IndexedEvent::decode(&mut &value[..])?;
+			if event.block_number > to.min(indexed) {
+				break
+			}
+			found.push(event);
+		}
+
+		// Blocks above the checkpoint, walking the best chain back.
+		let mut recent = Vec::new();
+		let mut hash = info.best_hash;
+		while let Some(header) = self.client.header(hash)? {
+			if header.number <= floor {
+				break
+			}
+			if header.number <= to {
+				let cached = self.unfinalized.lock().get(&hash).map(|(_, events)| events.clone());
+				let events = match cached {
+					Some(events) => events,
+					None => block_events(&self.client, hash, header.number)?,
+				};
+				recent.push(events);
+			}
+			hash = header.parent_hash;
+		}
+		found.extend(
+			recent
+				.into_iter()
+				.rev()
+				.flatten()
+				.filter(|event| accounts(&event.event).contains(&account)),
+		);
+
+		Ok(found)
+	}
+}
+
+enum Notification {
+	Imported(Hash, BlockNumber),
+	Finalized,
+}
+
+/// Keep `indexer` up to date with the chain until the node shuts down.
+pub async fn run(indexer: Arc<Indexer>) {
+	let imported = indexer
+		.client
+		.import_notification_stream()
+		.map(|notification| Notification::Imported(notification.hash, notification.header.number));
+	let finalized = indexer.client.finality_notification_stream().map(|_| Notification::Finalized);
+	let mut notifications = stream::select(imported, finalized);
+
+	if let Err(e) = indexer.index_finalized() {
+		log::error!(target: LOG_TARGET, "Unable to index finalized blocks: {}", e);
+	}
+	while let Some(notification) = notifications.next().await {
+		match notification {
+			Notification::Imported(hash, number) => indexer.import(hash, number),
+			Notification::Finalized =>
+				if let Err(e) = indexer.index_finalized() {
+					log::error!(target: LOG_TARGET, "Unable to index finalized blocks: {}", e);
+				},
+		}
+	}
+}
+
+/// Query the username event index.
+#[rpc(server)]
+pub trait UsernameEventsApi {
+	/// Username events involving `account` from `from_block` to `to_block`, both included.
+	///
+	/// The range defaults to the whole chain up to the best block.
+	#[method(name = "usernameStorage_events", blocking)]
+	fn events(
+		&self,
+		account: AccountId,
+		from_block: Option<BlockNumber>,
+		to_block: Option<BlockNumber>,
+	) -> RpcResult<Vec<Value>>;
+}
+
+/// Serves `usernameStorage_events` from an [`Indexer`].
+pub struct UsernameEvents {
+	indexer: Arc<Indexer>,
+}
+
+impl UsernameEvents {
+	pub fn new(indexer: Arc<Indexer>) -> Self {
+		Self { indexer }
+	}
+}
+
+impl UsernameEventsApiServer for UsernameEvents {
+	fn events(
+		&self,
+		account: AccountId,
+		from_block: Option<BlockNumber>,
+		to_block: Option<BlockNumber>,
+	) -> RpcResult<Vec<Value>> {
+		let to_block = to_block.unwrap_or_else(|| self.indexer.client.info().best_number);
+		let events = self
+			.indexer
+			.events(&account, from_block.unwrap_or_default(), to_block)
+			.map_err(|e| RpcError::Indexer(e.to_string()))?;
+		Ok(events.into_iter().map(|event| event_json(&account, event)).collect())
+	}
+}
+
+/// The JSON form of `event`, as seen from `account`.
+fn event_json(account: &AccountId, event: IndexedEvent) -> Value {
+	use UsernameEvent::*;
+
+	// The party of a two-account event that is not `account`.
+	let other = |a: AccountId, b: AccountId| if &a == account { b } else { a };
+	let (name, username, counterparty, price) = match event.event {
+		UsernameSet { username, .. } => ("UsernameSet", Some(username), None, None),
+		UsernameUpdated { username, .. } => ("UsernameUpdated", Some(username), None, None),
+		UsernameRetrieved { who, target, username } =>
+			("UsernameRetrieved", Some(username), Some(other(who, target)), None),
+		UsernameCleared { .. } => ("UsernameCleared", None, None, None),
+		UsernameForceCleared { username, .. } =>
+			("UsernameForceCleared", Some(username), None, None),
+		RegistrationSponsored { .. } => ("RegistrationSponsored", None, None, None),
+		UsernameTransferred { from, to, username } =>
+			("UsernameTransferred", Some(username), Some(other(from, to)), None),
+		UsernameListed { username, price, .. } =>
+			("UsernameListed", Some(username), None, Some(price)),
+		UsernameDelisted { username, .. } => ("UsernameDelisted", Some(username), None, None),
+		UsernameSold { seller, buyer, username, price } =>
+			("UsernameSold", Some(username), Some(other(seller, buyer)), Some(price)),
+		UsernameChangeScheduled { username, .. } =>
+			("UsernameChangeScheduled", Some(username), None, None),
+		UsernameChangeCancelled { username, .. } =>
+			("UsernameChangeCancelled", Some(username), None, None),
+		_ => ("Unknown", None, None, None),
+	};
+
+	json!({
+		"blockNumber": event.block_number,
+		"blockHash": event.block_hash,
+		"eventIndex": event.event_index,
+		"registry": event.registry,
+		"event": name,
+		"username": username.map(|username| Username::from(username)),
+		"counterparty": counterparty.map(|account| account.to_ss58check()),
+		"price": price.map(|price| price.to_string()),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn resume_after_checkpoint() {
+		let hash = Hash::repeat_byte(1);
+		assert_eq!(resume_at(None, None), Some(1));
+		assert_eq!(resume_at(Some((10, hash)), Some(hash)), Some(11));
+	}
+
+	#[test]
+	fn rebuild_after_revert() {
+		let hash = Hash::repeat_byte(1);
+		assert_eq!(resume_at(Some((10, hash)), Some(Hash::repeat_byte(2))), None);
+		assert_eq!(resume_at(Some((10, hash)), None), None);
+	}
+
+	#[test]
+	fn start_at_oldest_state() {
+		// Archive node.
+		assert_eq!(oldest_with_state(1, 1000, |_| true), 1);
+		// State of blocks below #745 is pruned.
+		assert_eq!(oldest_with_state(1, 1000, |number| number >= 745), 745);
+		assert_eq!(oldest_with_state(800, 1000, |number| number >= 745), 800);
+		// Nothing to index.
+		assert_eq!(oldest_with_state(1, 0, |_| true), 1);
+		assert_eq!(oldest_with_state(1, 1000, |_| false), 1001);
+	}
+
+	#[test]
+	fn walk_blocks_above_checkpoint() {
+		assert_eq!(walk_floor(0, 0), 0);
+		assert_eq!(walk_floor(100, 0), 100);
+		assert_eq!(walk_floor(100, 50), 100);
+		assert_eq!(walk_floor(100, 150), 149);
+	}
+}
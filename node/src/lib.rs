@@ -0,0 +1,15 @@
+//! Substrate Node Template CLI library.
+
+mod allowlist;
+mod benchmarking;
+mod chain_spec;
+pub mod cli;
+pub mod command;
+mod events;
+mod gateway;
+mod indexer;
+mod metrics;
+mod rpc;
+pub mod sealing;
+pub mod service;
+mod usernames;
@@ -1,13 +1,6 @@
-//! Substrate Node Template CLI library.
+//! Substrate Node Template CLI.
 #![warn(missing_docs)]
 
-mod benchmarking;
-mod chain_spec;
-mod cli;
-mod command;
-mod rpc;
-mod service;
-
 fn main() -> sc_cli::Result<()> {
-	command::run()
+	solochain_template_node::command::run()
 }
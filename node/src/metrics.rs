@@ -0,0 +1,163 @@
+//! Prometheus metrics of username activity on chain.
+
+use crate::{
+	events::{events_at, username_event, UsernameEvent},
+	service::FullClient,
+};
+use futures::StreamExt;
+use pallet_username_storage::UsernameStorageApi;
+use sc_client_api::BlockchainEvents;
+use solochain_template_runtime::{opaque::Block, AccountId, Hash, RuntimeEvent};
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use std::{collections::BTreeMap, sync::Arc};
+use substrate_prometheus_endpoint::{
+	register, CounterVec, GaugeVec, Opts, PrometheusError, Registry, U64,
+};
+use username_storage_rpc::PalletError;
+
+const LOG_TARGET: &str = "username-metrics";
+
+/// Version of `UsernameStorageApi` exposing the on-chain username counter.
+const COUNT_API_VERSION: u32 = 4;
+/// Version of `UsernameStorageApi` exposing the username counter of every registry.
+const REGISTRY_COUNT_API_VERSION: u32 = 7;
+/// Name of the default registry, the only one of runtimes older than `REGISTRY_COUNT_API_VERSION`.
+const DEFAULT_REGISTRY: &str = "users";
+/// Values of the `kind` label of `username_block_changes`.
+const CHANGE_KINDS: [&str; 4] = ["set", "updated", "cleared", "transferred"];
+
+/// Chain level username metrics, updated on every new best block and labelled by registry.
+pub struct ChainMetrics {
+	registered: GaugeVec<U64>,
+	block_changes: GaugeVec<U64>,
+	rejected: CounterVec<U64>,
+}
+
+impl ChainMetrics {
+	/// Register the metrics in `registry`.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			registered: register(
+				GaugeVec::new(
+					Opts::new(
+						"username_registered",
+						"Number of accounts with a username, by registry",
+					),
+					&["registry"],
+				)?,
+				registry,
+			)?,
+			block_changes: register(
+				GaugeVec::new(
+					Opts::new(
+						"username_block_changes",
+						"Username changes in the best block, by registry and kind of change",
+					),
+					&["registry", "kind"],
+				)?,
+				registry,
+			)?,
+			rejected: register(
+				CounterVec::new(
+					Opts::new(
+						"username_rejected_total",
+						"Username storage calls that failed in a best block, by pallet error",
+					),
+					&["reason"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	fn update(&self, client: &FullClient, hash: Hash) {
+		// Every registry shows up in the change metrics, even in blocks without changes.
+		let mut changes: BTreeMap<String, [u64; CHANGE_KINDS.len()]> = BTreeMap::new();
+		match username_counts(client, hash) {
+			Ok(counts) =>
+				for (registry, count) in counts {
+					if let Some(count) = count {
+						self.registered.with_label_values(&[&registry]).set(count.into());
+					}
+					changes.insert(registry, Default::default());
+				},
+			Err(e) => log::debug!(target: LOG_TARGET, "Unable to read username count: {}", e),
+		}
+
+		let events = match events_at(client, hash) {
+			Ok(events) => events,
+			Err(e) => {
+				log::debug!(target: LOG_TARGET, "Unable to read events of {:?}: {}", hash, e);
+				return
+			},
+		};
+
+		for record in events {
+			if let RuntimeEvent::System(frame_system::Event::ExtrinsicFailed {
+				dispatch_error,
+				..
+			}) = record.event
+			{
+				if let Some(error) = PalletError::from_dispatch_error(dispatch_error) {
+					self.rejected.with_label_values(&[error.name()]).inc();
+				}
+				continue
+			}
+
+			let Some((registry, event)) = username_event(record.event) else { continue };
+			// Index of the kind in `CHANGE_KINDS`.
+			let kind = match event {
+				UsernameEvent::UsernameSet { .. } => 0,
+				UsernameEvent::UsernameUpdated { .. } => 1,
+				UsernameEvent::UsernameCleared { .. } |
+				UsernameEvent::UsernameForceCleared { .. } => 2,
+				UsernameEvent::UsernameTransferred { .. } => 3,
+				_ => continue,
+			};
+			changes.entry(registry.into()).or_default()[kind] += 1;
+		}
+
+		for (registry, counts) in changes {
+			for (kind, count) in CHANGE_KINDS.into_iter().zip(counts) {
+				self.block_changes.with_label_values(&[&registry, kind]).set(count);
+			}
+		}
+	}
+}
+
+/// The registries of the runtime at `hash` with their username count, `None` when the runtime
+/// does not count the usernames of a registry.
+fn username_counts(
+	client: &FullClient,
+	hash: Hash,
+) -> Result<Vec<(String, Option<u32>)>, sp_api::ApiError> {
+	let api = client.runtime_api();
+	let version =
+		api.api_version::<dyn UsernameStorageApi<Block, AccountId>>(hash)?.unwrap_or_default();
+
+	if version >= REGISTRY_COUNT_API_VERSION {
+		api.registries(hash)?
+			.into_iter()
+			.map(|registry| {
+				let count = api.registry_username_count(hash, registry.clone())?;
+				Ok((String::from_utf8_lossy(&registry).into_owned(), count))
+			})
+			.collect()
+	} else if version >= COUNT_API_VERSION {
+		Ok(vec![(DEFAULT_REGISTRY.into(), Some(api.username_count(hash)?))])
+	} else {
+		Ok(vec![(DEFAULT_REGISTRY.into(), None)])
+	}
+}
+
+/// Update `metrics` on every new best block until the node shuts down.
+///
+/// Blocks that are imported again after a re-org are counted again in `username_rejected_total`.
+pub async fn run(client: Arc<FullClient>, metrics: ChainMetrics) {
+	let mut imports = client.import_notification_stream();
+	while let Some(notification) = imports.next().await {
+		if notification.is_new_best {
+			metrics.update(&client, notification.hash);
+		}
+	}
+}
@@ -8,12 +8,19 @@
 use std::sync::Arc;
 
 use jsonrpsee::RpcModule;
+use sc_client_api::{BlockBackend, BlockchainEvents};
+use sc_rpc::SubscriptionTaskExecutor;
 use sc_transaction_pool_api::TransactionPool;
 use solochain_template_runtime::{opaque::Block, AccountId, Balance, Nonce};
 use sp_api::ProvideRuntimeApi;
 use sp_block_builder::BlockBuilder;
 use sp_blockchain::{Error as BlockChainError, HeaderBackend, HeaderMetadata};
-use username_storage_rpc::UsernameStorageRpc;
+use username_storage_rpc::{Discover, RpcMetrics, UsernameStorageRpc, DEFAULT_CACHE_SIZE};
+
+use crate::{
+    indexer::{Indexer, UsernameEvents},
+    sealing::CommandSink,
+};
 
 /// Full client dependencies.
 pub struct FullDeps<C, P> {
@@ -21,6 +28,14 @@ pub struct FullDeps<C, P> {
     pub client: Arc<C>,
     /// Transaction pool instance.
     pub pool: Arc<P>,
+    /// Executor for RPC subscription tasks.
+    pub subscription_executor: SubscriptionTaskExecutor,
+    /// Username storage RPC metrics, when Prometheus is enabled.
+    pub metrics: Option<RpcMetrics>,
+    /// Username event index, when enabled.
+    pub indexer: Option<Arc<Indexer>>,
+    /// Channel to the manual sealing task, when `--sealing` is set.
+    pub manual_seal: Option<CommandSink>,
 }
 
 /// Instantiate all full RPC extensions.
@@ -30,6 +45,7 @@ pub fn create_full<C, P>(
 where
     C: ProvideRuntimeApi<Block>,
     C: HeaderBackend<Block> + HeaderMetadata<Block, Error = BlockChainError> + 'static,
+    C: BlockBackend<Block> + BlockchainEvents<Block>,
     C: Send + Sync + 'static,
     C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Nonce>,
     C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
@@ -37,19 +53,203 @@ where
     C::Api: pallet_username_storage::UsernameStorageApi<Block, AccountId>,
     P: TransactionPool + 'static,
 {
+    use crate::indexer::UsernameEventsApiServer;
     use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApiServer};
+    use sc_consensus_manual_seal::rpc::{ManualSeal, ManualSealApiServer};
     use substrate_frame_rpc_system::{System, SystemApiServer};
-    use username_storage_rpc::UsernameStorageRpcApiServer;
+    use username_storage_rpc::{DiscoverApiServer, UsernameStorageRpcApiServer};
 
     let mut module = RpcModule::new(());
-    let FullDeps { client, pool } = deps;
+    let FullDeps {
+        client,
+        pool,
+        subscription_executor,
+        metrics,
+        indexer,
+        manual_seal,
+    } = deps;
 
     module.merge(System::new(client.clone(), pool).into_rpc())?;
     module.merge(TransactionPayment::new(client.clone()).into_rpc())?;
 
     // Register username storage RPC
-    let username_storage_rpc = UsernameStorageRpc::new(client.clone());
+    let mut username_storage_rpc = UsernameStorageRpc::new(client.clone(), subscription_executor)
+        .with_cache_size(DEFAULT_CACHE_SIZE);
+    if let Some(metrics) = metrics {
+        username_storage_rpc = username_storage_rpc.with_metrics(metrics);
+    }
     module.merge(username_storage_rpc.into_rpc())?;
+    module.merge(Discover::new().into_rpc())?;
+    if let Some(indexer) = indexer {
+        module.merge(UsernameEvents::new(indexer).into_rpc())?;
+    }
+    if let Some(sink) = manual_seal {
+        module.merge(ManualSeal::new(sink).into_rpc())?;
+    }
 
     Ok(module)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::UsernameEventsApiServer;
+    use jsonrpsee::{
+        core::RpcResult,
+        types::{error::INVALID_PARAMS_CODE, ErrorObjectOwned},
+        PendingSubscriptionSink,
+    };
+    use serde_json::{json, Value};
+    use solochain_template_runtime::{BlockNumber, Hash};
+    use sp_core::crypto::Ss58Codec;
+    use std::collections::BTreeSet;
+    use username_storage_rpc::{openrpc, DiscoverApiServer, Username, UsernameStorageRpcApiServer};
+
+    /// Registers the username methods of `create_full` without a client behind them.
+    struct Stub;
+
+    fn called() -> ErrorObjectOwned {
+        ErrorObjectOwned::owned(1, "called", None::<()>)
+    }
+
+    impl UsernameStorageRpcApiServer<AccountId, Hash> for Stub {
+        fn get_username(
+            &self,
+            _: AccountId,
+            _: Option<Hash>,
+            _: Option<bool>,
+            _: Option<String>,
+        ) -> RpcResult<Option<Username>> {
+            Err(called())
+        }
+
+        fn get_usernames(
+            &self,
+            _: Vec<AccountId>,
+            _: Option<Hash>,
+            _: Option<bool>,
+            _: Option<String>,
+        ) -> RpcResult<Vec<Option<Username>>> {
+            Err(called())
+        }
+
+        fn set_username(
+            &self,
+            _: AccountId,
+            _: String,
+            _: Option<Hash>,
+            _: Option<bool>,
+            _: Option<String>,
+        ) -> RpcResult<()> {
+            Err(called())
+        }
+
+        fn registries(&self, _: Option<Hash>, _: Option<bool>) -> RpcResult<Vec<String>> {
+            Err(called())
+        }
+
+        fn subscribe_username(
+            &self,
+            _: PendingSubscriptionSink,
+            _: Vec<AccountId>,
+            _: Option<bool>,
+            _: Option<String>,
+        ) {
+        }
+    }
+
+    impl UsernameEventsApiServer for Stub {
+        fn events(
+            &self,
+            _: AccountId,
+            _: Option<BlockNumber>,
+            _: Option<BlockNumber>,
+        ) -> RpcResult<Vec<Value>> {
+            Err(called())
+        }
+    }
+
+    fn module() -> RpcModule<()> {
+        let mut module = RpcModule::new(());
+        module
+            .merge(UsernameStorageRpcApiServer::into_rpc(Stub))
+            .unwrap();
+        module
+            .merge(UsernameEventsApiServer::into_rpc(Stub))
+            .unwrap();
+        module.merge(Discover::new().into_rpc()).unwrap();
+        module
+    }
+
+    /// A valid value of the documented parameter `param`.
+    fn valid_value(param: &Value) -> Value {
+        if param["required"] != json!(true) {
+            return Value::Null;
+        }
+        let account = json!(AccountId::from([1; 32]).to_ss58check());
+        match param["schema"]["type"].as_str() {
+            Some("array") => json!([account]),
+            Some("string") => json!("alice"),
+            _ => account,
+        }
+    }
+
+    /// The error code of calling `method` with `params`, if any.
+    async fn error_code(module: &RpcModule<()>, method: &str, params: Vec<Value>) -> Option<i64> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let (response, _) = module
+            .raw_json_request(&request.to_string(), 1)
+            .await
+            .unwrap();
+        let response: Value = serde_json::from_str(&response).unwrap();
+        response["error"]["code"].as_i64()
+    }
+
+    #[test]
+    fn openrpc_methods_are_registered() {
+        let document = openrpc::document();
+        let documented: BTreeSet<&str> = document["methods"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|method| method["name"].as_str().unwrap())
+            .collect();
+        let module = module();
+        let registered: BTreeSet<&str> = module
+            .method_names()
+            .filter(|name| name.starts_with("usernameStorage_"))
+            .collect();
+
+        assert_eq!(documented, registered);
+    }
+
+    #[tokio::test]
+    async fn openrpc_params_match() {
+        let document = openrpc::document();
+        let module = module();
+        for method in document["methods"].as_array().unwrap() {
+            let name = method["name"].as_str().unwrap();
+            let params = method["params"].as_array().unwrap();
+            let valid: Vec<Value> = params.iter().map(valid_value).collect();
+
+            assert_ne!(
+                error_code(&module, name, valid.clone()).await,
+                Some(INVALID_PARAMS_CODE as i64),
+                "{}: documented parameters are rejected",
+                name
+            );
+            // The method takes a parameter at each documented position.
+            for (index, param) in params.iter().enumerate() {
+                let mut invalid = valid.clone();
+                invalid[index] = json!({ "invalid": true });
+                assert_eq!(
+                    error_code(&module, name, invalid).await,
+                    Some(INVALID_PARAMS_CODE as i64),
+                    "{}: parameter {} is not taken",
+                    name,
+                    param["name"]
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Manual and instant sealing for development and tests.
+//!
+//! With `--sealing`, blocks are authored by `sc-consensus-manual-seal` instead of Aura and
+//! finalized right away instead of by GRANDPA. Blocks can always be requested through the
+//! `engine_createBlock` and `engine_finalizeBlock` RPCs, on top of what the sealing mode produces
+//! on its own.
+//!
+//! The runtime still expects Aura slots, so every block carries an Aura pre-digest and a timestamp
+//! in a later slot than its parent, even when several blocks are sealed within a slot. The slot of
+//! the parent is read from its header, so this holds across restarts of the node.
+
+use crate::service::{FullClient, FullPool, FullSelectChain};
+use futures::{channel::mpsc, stream, Stream, StreamExt};
+use sc_consensus_manual_seal::{
+	consensus::aura::AuraConsensusDataProvider, EngineCommand, ManualSealParams,
+};
+use sc_service::{error::Error as ServiceError, TaskManager};
+use sc_telemetry::TelemetryHandle;
+use sc_transaction_pool_api::TransactionPool;
+use solochain_template_runtime::{opaque::Block, Hash};
+use sp_blockchain::HeaderBackend;
+use sp_consensus_aura::sr25519::AuthoritySignature;
+use std::{
+	str::FromStr,
+	sync::Arc,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use substrate_prometheus_endpoint::Registry;
+
+/// Number of pending `engine_*` commands.
+const COMMAND_BUFFER: usize = 1024;
+
+/// How blocks are produced when manual sealing is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sealing {
+	/// Seal a block as soon as a transaction enters the pool.
+	Instant,
+	/// Only seal blocks requested through `engine_createBlock`.
+	Manual,
+	/// Seal a block, empty or not, at a fixed interval.
+	Interval(Duration),
+}
+
+impl FromStr for Sealing {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"instant" => Ok(Self::Instant),
+			"manual" => Ok(Self::Manual),
+			_ => match s.strip_prefix("interval=") {
+				Some(ms) => match ms.parse::<u64>() {
+					Ok(ms) if ms > 0 => Ok(Self::Interval(Duration::from_millis(ms))),
+					_ => Err(format!("invalid sealing interval `{}`, expected milliseconds", ms)),
+				},
+				None => Err(format!(
+					"unknown sealing mode `{}`, expected `instant`, `manual` or `interval=<ms>`",
+					s
+				)),
+			},
+		}
+	}
+}
+
+/// Sender of the `engine_*` RPC commands.
+pub type CommandSink = mpsc::Sender<EngineCommand<Hash>>;
+
+/// The channel carrying `engine_*` RPC commands to the sealing task.
+pub fn command_channel() -> (CommandSink, mpsc::Receiver<EngineCommand<Hash>>) {
+	mpsc::channel(COMMAND_BUFFER)
+}
+
+fn seal_command(create_empty: bool) -> EngineCommand<Hash> {
+	EngineCommand::SealNewBlock { create_empty, finalize: true, parent_hash: None, sender: None }
+}
+
+/// The timestamp of a block in the slot after `parent_slot`, or later at time `now`.
+fn next_timestamp(now: u64, parent_slot: u64, slot_duration: u64) -> u64 {
+	now.max((parent_slot + 1) * slot_duration)
+}
+
+/// The timestamp of a block built on `parent`.
+fn timestamp(
+	client: &FullClient,
+	parent: Hash,
+	slot_duration: u64,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+	let header = client.header(parent)?.ok_or("Unknown parent block")?;
+	let slot = sc_consensus_aura::find_pre_digest::<Block, AuthoritySignature>(&header)
+		.map_err(|e| e.to_string())?;
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.expect("Current time is after the UNIX epoch; qed")
+		.as_millis() as u64;
+	Ok(next_timestamp(now, *slot, slot_duration))
+}
+
+/// Start sealing blocks in `sealing` mode and on `rpc_commands`.
+#[allow(clippy::too_many_arguments)]
+pub fn start(
+	sealing: Sealing,
+	task_manager: &TaskManager,
+	client: Arc<FullClient>,
+	pool: Arc<FullPool>,
+	select_chain: FullSelectChain,
+	rpc_commands: mpsc::Receiver<EngineCommand<Hash>>,
+	prometheus_registry: Option<&Registry>,
+	telemetry: Option<TelemetryHandle>,
+) -> Result<(), ServiceError> {
+	let mut commands: Vec<stream::BoxStream<'static, EngineCommand<Hash>>> =
+		vec![rpc_commands.boxed()];
+	match sealing {
+		Sealing::Instant =>
+			commands.push(pool.import_notification_stream().map(|_| seal_command(false)).boxed()),
+		Sealing::Manual => {},
+		Sealing::Interval(period) => commands.push(interval(period).boxed()),
+	}
+
+	let proposer_factory = sc_basic_authorship::ProposerFactory::new(
+		task_manager.spawn_handle(),
+		client.clone(),
+		pool.clone(),
+		prometheus_registry,
+		telemetry,
+	);
+
+	let slot_duration = sc_consensus_aura::slot_duration(&*client)?.as_millis();
+	let timestamp_client = client.clone();
+
+	let authorship = sc_consensus_manual_seal::run_manual_seal(ManualSealParams {
+		block_import: client.clone(),
+		env: proposer_factory,
+		client: client.clone(),
+		pool,
+		commands_stream: stream::select_all(commands),
+		select_chain,
+		consensus_data_provider: Some(Box::new(AuraConsensusDataProvider::new(client))),
+		create_inherent_data_providers: move |parent, ()| {
+			let client = timestamp_client.clone();
+			async move {
+				let timestamp = timestamp(&client, parent, slot_duration)?;
+				Ok(sp_timestamp::InherentDataProvider::new(timestamp.into()))
+			}
+		},
+	});
+
+	// Like the Aura authoring task, sealing is essential to the node.
+	task_manager.spawn_essential_handle().spawn_blocking(
+		"manual-seal",
+		Some("block-authoring"),
+		authorship,
+	);
+	Ok(())
+}
+
+/// A seal command every `period`.
+fn interval(period: Duration) -> impl Stream<Item = EngineCommand<Hash>> {
+	stream::unfold((), move |()| async move {
+		tokio::time::sleep(period).await;
+		Some((seal_command(true), ()))
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sealing_from_str() {
+		assert_eq!("instant".parse::<Sealing>(), Ok(Sealing::Instant));
+		assert_eq!("manual".parse::<Sealing>(), Ok(Sealing::Manual));
+		assert_eq!(
+			"interval=6000".parse::<Sealing>(),
+			Ok(Sealing::Interval(Duration::from_millis(6000)))
+		);
+	}
+
+	#[test]
+	fn timestamp_after_parent_slot() {
+		// Wall time is used when past the slot of the parent
+		assert_eq!(next_timestamp(20_000, 2, 6000), 20_000);
+		// Otherwise, the start of the next slot, as after sealing several blocks in a slot or
+		// restarting the node before the clock caught up
+		assert_eq!(next_timestamp(13_000, 2, 6000), 18_000);
+		assert_eq!(next_timestamp(13_000, 10, 6000), 66_000);
+		// Genesis has slot 0
+		assert_eq!(next_timestamp(0, 0, 6000), 6000);
+	}
+
+	#[test]
+	fn sealing_from_invalid_str() {
+		for interval in ["interval=", "interval=0", "interval=-1", "interval=1.5", "interval=6s"] {
+			let error = interval.parse::<Sealing>().unwrap_err();
+			assert!(error.starts_with("invalid sealing interval"), "{}: {}", interval, error);
+		}
+		for mode in ["", "Instant", "auto", "interval", "interval 6000", " manual"] {
+			let error = mode.parse::<Sealing>().unwrap_err();
+			assert!(error.starts_with("unknown sealing mode"), "{}: {}", mode, error);
+		}
+	}
+}
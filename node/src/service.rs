@@ -8,6 +8,7 @@ use sc_service::{error::Error as ServiceError, Configuration, TaskManager, WarpS
 use sc_telemetry::{Telemetry, TelemetryWorker};
 use sc_transaction_pool_api::OffchainTransactionPoolFactory;
 use solochain_template_runtime::{self, apis::RuntimeApi, opaque::Block};
+use sp_blockchain::HeaderBackend;
 use sp_consensus_aura::sr25519::AuthorityPair as AuraPair;
 use std::{sync::Arc, time::Duration};
 
@@ -17,7 +18,8 @@ pub(crate) type FullClient = sc_service::TFullClient<
 	sc_executor::WasmExecutor<sp_io::SubstrateHostFunctions>,
 >;
 type FullBackend = sc_service::TFullBackend<Block>;
-type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
+pub(crate) type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
+pub(crate) type FullPool = sc_transaction_pool::TransactionPoolHandle<Block, FullClient>;
 
 /// The minimum period of blocks on which justifications will be
 /// imported and generated.
@@ -28,7 +30,7 @@ pub type Service = sc_service::PartialComponents<
 	FullBackend,
 	FullSelectChain,
 	sc_consensus::DefaultImportQueue<Block>,
-	sc_transaction_pool::TransactionPoolHandle<Block, FullClient>,
+	FullPool,
 	(
 		sc_consensus_grandpa::GrandpaBlockImport<FullBackend, Block, FullClient, FullSelectChain>,
 		sc_consensus_grandpa::LinkHalf<Block, FullClient, FullSelectChain>,
@@ -37,6 +39,12 @@ pub type Service = sc_service::PartialComponents<
 >;
 
 pub fn new_partial(config: &Configuration) -> Result<Service, ServiceError> {
+	build_partial(config, false)
+}
+
+/// Build the partial components, with an import queue for manually sealed blocks when
+/// `manual_seal` is set.
+fn build_partial(config: &Configuration, manual_seal: bool) -> Result<Service, ServiceError> {
 	let telemetry = config
 		.telemetry_endpoints
 		.clone()
@@ -84,7 +92,13 @@ pub fn new_partial(config: &Configuration) -> Result<Service, ServiceError> {
 	)?;
 
 	let cidp_client = client.clone();
-	let import_queue =
+	let import_queue = if manual_seal {
+		sc_consensus_manual_seal::import_queue(
+			Box::new(client.clone()),
+			&task_manager.spawn_essential_handle(),
+			config.prometheus_registry(),
+		)
+	} else {
 		sc_consensus_aura::import_queue::<AuraPair, _, _, _, _, _>(ImportQueueParams {
 			block_import: grandpa_block_import.clone(),
 			justification_import: Some(Box::new(grandpa_block_import.clone())),
@@ -112,7 +126,8 @@ pub fn new_partial(config: &Configuration) -> Result<Service, ServiceError> {
 			check_for_equivocation: Default::default(),
 			telemetry: telemetry.as_ref().map(|x| x.handle()),
 			compatibility_mode: Default::default(),
-		})?;
+		})?
+	};
 
 	Ok(sc_service::PartialComponents {
 		client,
@@ -130,7 +145,9 @@ pub fn new_partial(config: &Configuration) -> Result<Service, ServiceError> {
 pub fn new_full<
 	N: sc_network::NetworkBackend<Block, <Block as sp_runtime::traits::Block>::Hash>,
 >(
-	config: Configuration,
+	mut config: Configuration,
+	username: crate::cli::UsernameParams,
+	sealing: Option<crate::sealing::Sealing>,
 ) -> Result<TaskManager, ServiceError> {
 	let sc_service::PartialComponents {
 		client,
@@ -141,7 +158,14 @@ pub fn new_full<
 		select_chain,
 		transaction_pool,
 		other: (block_import, grandpa_link, mut telemetry),
-	} = new_partial(&config)?;
+	} = build_partial(&config, sealing.is_some())?;
+
+	// On a permissioned chain, refuse unknown peers from the start. The allowlist task then sets
+	// the reserved peers.
+	if crate::allowlist::allowed_peers(&client, client.info().finalized_hash)?.is_some() {
+		config.network.default_peers_set.non_reserved_mode =
+			sc_network::config::NonReservedPeerMode::Deny;
+	}
 
 	let mut net_config = sc_network::config::FullNetworkConfiguration::<
 		Block,
@@ -204,19 +228,62 @@ pub fn new_full<
 		);
 	}
 
+	task_manager.spawn_handle().spawn(
+		"node-allowlist",
+		None,
+		crate::allowlist::run(client.clone(), network.clone()),
+	);
+
 	let role = config.role;
 	let force_authoring = config.force_authoring;
 	let backoff_authoring_blocks: Option<()> = None;
 	let name = config.network.node_name.clone();
-	let enable_grandpa = !config.disable_grandpa;
+	// Manually sealed blocks are finalized by the sealing task.
+	let enable_grandpa = !config.disable_grandpa && sealing.is_none();
 	let prometheus_registry = config.prometheus_registry().cloned();
 
+	let rpc_metrics = prometheus_registry
+		.as_ref()
+		.map(username_storage_rpc::RpcMetrics::register)
+		.transpose()
+		.map_err(|e| ServiceError::Other(e.to_string()))?;
+
+	let indexer = if username.username_indexer {
+		let path = config.data_path.join("username-index");
+		let indexer = crate::indexer::Indexer::open(&path, client.clone()).map_err(|e| {
+			ServiceError::Other(format!(
+				"Unable to open the username index at {}: {}",
+				path.display(),
+				e
+			))
+		})?;
+		Some(Arc::new(indexer))
+	} else {
+		None
+	};
+
+	let (seal_sink, seal_commands) = match sealing {
+		Some(_) => {
+			let (sink, commands) = crate::sealing::command_channel();
+			(Some(sink), Some(commands))
+		},
+		None => (None, None),
+	};
+
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
-
-		Box::new(move |_| {
-			let deps = crate::rpc::FullDeps { client: client.clone(), pool: pool.clone() };
+		let indexer = indexer.clone();
+
+		Box::new(move |subscription_executor| {
+			let deps = crate::rpc::FullDeps {
+				client: client.clone(),
+				pool: pool.clone(),
+				subscription_executor,
+				metrics: rpc_metrics.clone(),
+				indexer: indexer.clone(),
+				manual_seal: seal_sink.clone(),
+			};
 			crate::rpc::create_full(deps).map_err(Into::into)
 		})
 	};
@@ -236,7 +303,45 @@ pub fn new_full<
 		telemetry: telemetry.as_mut(),
 	})?;
 
-	if role.is_authority() {
+	if let Some(registry) = prometheus_registry.as_ref() {
+		let metrics = crate::metrics::ChainMetrics::register(registry)
+			.map_err(|e| ServiceError::Other(e.to_string()))?;
+		task_manager.spawn_handle().spawn(
+			"username-metrics",
+			None,
+			crate::metrics::run(client.clone(), metrics),
+		);
+	}
+
+	if let Some(indexer) = indexer {
+		task_manager.spawn_handle().spawn_blocking(
+			"username-indexer",
+			None,
+			crate::indexer::run(indexer),
+		);
+	}
+
+	if let Some(addr) = username.username_gateway {
+		crate::gateway::spawn(
+			task_manager.spawn_handle(),
+			addr,
+			client.clone(),
+			transaction_pool.clone(),
+		)?;
+	}
+
+	if let (Some(sealing), Some(commands)) = (sealing, seal_commands) {
+		crate::sealing::start(
+			sealing,
+			&task_manager,
+			client,
+			transaction_pool.clone(),
+			select_chain,
+			commands,
+			prometheus_registry.as_ref(),
+			telemetry.as_ref().map(|x| x.handle()),
+		)?;
+	} else if role.is_authority() {
 		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
 			task_manager.spawn_handle(),
 			client.clone(),
@@ -331,3 +436,18 @@ pub fn new_full<
 
 	Ok(task_manager)
 }
+
+/// Builds a new service for a full client, on the network backend selected in `config`.
+pub fn new_full_node(
+	config: Configuration,
+	username: crate::cli::UsernameParams,
+	sealing: Option<crate::sealing::Sealing>,
+) -> Result<TaskManager, ServiceError> {
+	match config.network.network_backend.unwrap_or_default() {
+		sc_network::config::NetworkBackendType::Libp2p => new_full::<
+			sc_network::NetworkWorker<Block, <Block as sp_runtime::traits::Block>::Hash>,
+		>(config, username, sealing),
+		sc_network::config::NetworkBackendType::Litep2p =>
+			new_full::<sc_network::Litep2pNetworkBackend>(config, username, sealing),
+	}
+}
@@ -0,0 +1,496 @@
+//! `export-usernames` and `import-usernames` subcommands.
+//!
+//! Exports read the `Usernames` map of a registry straight from the local database, so they work on
+//! a stopped node. Imports turn an export, or any file in the same format, into a genesis patch for
+//! the pallet of a registry that can be merged into a chain spec produced by `build-spec`. Both
+//! take the registry with `--registry`, the default `users` registry when not given.
+//!
+//! Both commands share two formats. JSON is an array of `{ "account", "username" }` objects, with
+//! usernames encoded as by the RPC. CSV has an `account,username,encoding` header, the encoding is
+//! `utf8`, or `hex` for names that are not printable UTF-8. Fields holding commas, quotes or line
+//! breaks are quoted as in RFC 4180.
+
+use crate::service::FullClient;
+use codec::Decode;
+use frame_support::storage::StoragePrefixedMap;
+use pallet_username_storage::{Config, UsernameRules, Usernames};
+use sc_cli::{BlockNumberOrHash, CliConfiguration, DatabaseParams, PruningParams, SharedParams};
+use sc_client_api::StorageProvider;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solochain_template_runtime::{
+	configs::{BotRegistry, OrganizationRegistry},
+	opaque::Block,
+	AccountId, Runtime,
+};
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	bytes::{from_hex, to_hex},
+	crypto::Ss58Codec,
+	storage::StorageKey,
+	Get,
+};
+use std::{
+	collections::HashSet,
+	fs,
+	io::{self, Write},
+	path::{Path, PathBuf},
+};
+use username_storage_rpc::Username;
+
+const CSV_HEADER: &str = "account,username,encoding";
+
+/// File format of a username list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+	Json,
+	Csv,
+}
+
+/// A username registry of the runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Registry {
+	Users,
+	Bots,
+	Organizations,
+}
+
+impl Registry {
+	/// Name of the registry, as listed by `usernameStorage_registries`.
+	fn name(self) -> &'static str {
+		match self {
+			Registry::Users => "users",
+			Registry::Bots => "bots",
+			Registry::Organizations => "organizations",
+		}
+	}
+
+	/// Key of the registry pallet in the runtime genesis config.
+	fn genesis_key(self) -> &'static str {
+		match self {
+			Registry::Users => "usernameStorage",
+			Registry::Bots => "botUsernames",
+			Registry::Organizations => "organizationUsernames",
+		}
+	}
+
+	/// Storage prefix of the `Usernames` map of the registry.
+	fn usernames_prefix(self) -> [u8; 32] {
+		match self {
+			Registry::Users => Usernames::<Runtime>::final_prefix(),
+			Registry::Bots => Usernames::<Runtime, BotRegistry>::final_prefix(),
+			Registry::Organizations => Usernames::<Runtime, OrganizationRegistry>::final_prefix(),
+		}
+	}
+
+	/// Check the length and the rules of the registry, as its genesis build does.
+	fn check(self, username: &[u8]) -> Result<(), String> {
+		match self {
+			Registry::Users => check_username::<()>(username),
+			Registry::Bots => check_username::<BotRegistry>(username),
+			Registry::Organizations => check_username::<OrganizationRegistry>(username),
+		}
+	}
+}
+
+fn check_username<I: 'static>(username: &[u8]) -> Result<(), String>
+where
+	Runtime: Config<I>,
+{
+	let max_length = <Runtime as Config<I>>::MaxUsernameLength::get() as usize;
+	if username.is_empty() || username.len() > max_length {
+		return Err(format!("must be 1 to {} bytes long", max_length))
+	}
+	if !<Runtime as Config<I>>::UsernameRules::is_allowed(username) {
+		return Err("is not allowed".into())
+	}
+	Ok(())
+}
+
+/// A username and the account owning it.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+	account: AccountId,
+	username: Username,
+}
+
+/// The `export-usernames` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ExportUsernamesCmd {
+	/// Block to export, by number or hash. Defaults to the best block.
+	#[arg(long, value_name = "HASH or NUMBER")]
+	pub at: Option<BlockNumberOrHash>,
+
+	/// Registry to export.
+	#[arg(long, value_enum, default_value_t = Registry::Users)]
+	pub registry: Registry,
+
+	/// Output format.
+	#[arg(long, value_enum, default_value_t = Format::Json)]
+	pub format: Format,
+
+	/// Write to this file instead of the standard output.
+	#[arg(long, short)]
+	pub output: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl ExportUsernamesCmd {
+	/// Export the usernames stored in `client` at the requested block.
+	pub fn run(&self, client: &FullClient) -> sc_cli::Result<()> {
+		let hash = match &self.at {
+			Some(at) => client
+				.block_hash_from_id(&at.parse::<Block>()?)?
+				.ok_or_else(|| format!("Block {:?} not found", at))?,
+			None => client.info().best_hash,
+		};
+
+		let prefix = StorageKey(self.registry.usernames_prefix().to_vec());
+		let mut entries = Vec::new();
+		for (key, value) in client.storage_pairs(hash, Some(&prefix), None)? {
+			// Keys end with `blake2_128(account) ++ account`.
+			let account = AccountId::decode(&mut &key.0[prefix.0.len() + 16..])
+				.map_err(|e| format!("Invalid username key: {}", e))?;
+			let username = Vec::<u8>::decode(&mut &value.0[..])
+				.map_err(|e| format!("Invalid username of {}: {}", account, e))?;
+			entries.push(Entry { account, username: username.into() });
+		}
+
+		let mut out: Box<dyn Write> = match &self.output {
+			Some(path) => Box::new(io::BufWriter::new(fs::File::create(path)?)),
+			None => Box::new(io::stdout().lock()),
+		};
+		match self.format {
+			Format::Json => {
+				serde_json::to_writer_pretty(&mut out, &entries)
+					.map_err(|e| format!("Unable to write JSON: {}", e))?;
+				writeln!(out)?;
+			},
+			Format::Csv => {
+				writeln!(out, "{}", CSV_HEADER)?;
+				for entry in entries {
+					writeln!(out, "{}", csv_line(entry))?;
+				}
+			},
+		}
+		out.flush()?;
+		Ok(())
+	}
+}
+
+impl CliConfiguration for ExportUsernamesCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+/// The `import-usernames` command.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct ImportUsernamesCmd {
+	/// File to import, as written by `export-usernames`.
+	pub input: PathBuf,
+
+	/// Registry to import into.
+	#[arg(long, value_enum, default_value_t = Registry::Users)]
+	pub registry: Registry,
+
+	/// Input format. Defaults to CSV for `.csv` files and to JSON otherwise.
+	#[arg(long, value_enum)]
+	pub format: Option<Format>,
+
+	/// Write the genesis patch to this file instead of the standard output.
+	#[arg(long, short)]
+	pub output: Option<PathBuf>,
+}
+
+impl ImportUsernamesCmd {
+	/// Check the usernames of the input file and write them as a genesis patch.
+	pub fn run(&self) -> sc_cli::Result<()> {
+		let format = self.format.unwrap_or_else(|| format_of(&self.input));
+		let contents = fs::read_to_string(&self.input)?;
+		let entries = match format {
+			Format::Json => serde_json::from_str::<Vec<Entry>>(&contents)
+				.map_err(|e| format!("Invalid JSON input: {}", e))?,
+			Format::Csv => parse_csv(&contents)?,
+		};
+
+		let patch = genesis_patch(self.registry, entries)?;
+		let patch = serde_json::to_string_pretty(&patch)
+			.map_err(|e| format!("Unable to write JSON: {}", e))?;
+		match &self.output {
+			Some(path) => fs::write(path, patch + "\n")?,
+			None => println!("{}", patch),
+		}
+		Ok(())
+	}
+}
+
+/// The genesis patch registering `entries` in `registry`, after checking that its genesis build
+/// accepts them.
+fn genesis_patch(registry: Registry, entries: Vec<Entry>) -> Result<Value, String> {
+	let mut accounts = HashSet::new();
+	let mut names = HashSet::new();
+	let mut usernames = Vec::with_capacity(entries.len());
+	for Entry { account, username } in entries {
+		let username = username.into_bytes();
+		let shown = String::from_utf8_lossy(&username).into_owned();
+		registry.check(&username).map_err(|reason| {
+			format!(
+				"Username {:?} of {} {} in the {} registry",
+				shown,
+				account,
+				reason,
+				registry.name()
+			)
+		})?;
+		if !accounts.insert(account.clone()) {
+			return Err(format!("Account {} has more than one username", account))
+		}
+		if !names.insert(username.clone()) {
+			return Err(format!("Username {:?} is listed more than once", shown))
+		}
+		usernames.push((account, username));
+	}
+
+	Ok(json!({ registry.genesis_key(): { "usernames": usernames } }))
+}
+
+/// The format of `path`, guessed from its extension.
+fn format_of(path: &Path) -> Format {
+	match path.extension() {
+		Some(extension) if extension.eq_ignore_ascii_case("csv") => Format::Csv,
+		_ => Format::Json,
+	}
+}
+
+fn csv_line(entry: Entry) -> String {
+	let account = entry.account.to_ss58check();
+	match entry.username {
+		Username::Utf8(name) if !name.chars().any(char::is_control) => {
+			if name.contains([',', '"']) {
+				format!("{},\"{}\",utf8", account, name.replace('"', "\"\""))
+			} else {
+				format!("{},{},utf8", account, name)
+			}
+		},
+		username => format!("{},{},hex", account, to_hex(&username.into_bytes(), false)),
+	}
+}
+
+/// Split CSV `contents` into records, following RFC 4180 quoting, along with the number of the
+/// line each record starts on. Quoted fields may hold commas, quotes and line breaks.
+fn csv_records(contents: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+	let mut records = Vec::new();
+	let mut fields = Vec::new();
+	let mut field = String::new();
+	let mut chars = contents.chars().peekable();
+	let mut quoted = false;
+	let (mut line, mut start) = (1, 1);
+	while let Some(c) = chars.next() {
+		match (c, quoted) {
+			('"', false) if field.is_empty() => quoted = true,
+			('"', true) if chars.peek() == Some(&'"') => {
+				chars.next();
+				field.push('"');
+			},
+			('"', true) => quoted = false,
+			(',', false) => fields.push(std::mem::take(&mut field)),
+			('\r', false) if chars.peek() == Some(&'\n') => {},
+			('\n', false) => {
+				fields.push(std::mem::take(&mut field));
+				records.push((start, std::mem::take(&mut fields)));
+				line += 1;
+				start = line;
+			},
+			(c, _) => {
+				if c == '\n' {
+					line += 1;
+				}
+				field.push(c);
+			},
+		}
+	}
+	if quoted {
+		return Err(format!("Invalid CSV line {}: unterminated quote", start))
+	}
+	if !field.is_empty() || !fields.is_empty() {
+		fields.push(field);
+		records.push((start, fields));
+	}
+	Ok(records)
+}
+
+fn parse_csv(contents: &str) -> sc_cli::Result<Vec<Entry>> {
+	let mut entries = Vec::new();
+	for (number, fields) in csv_records(contents)? {
+		if fields == [""] || (number == 1 && fields.join(",") == CSV_HEADER) {
+			continue
+		}
+		let invalid = |reason: String| format!("Invalid CSV line {}: {}", number, reason);
+
+		let (account, name, encoding) = match &fields[..] {
+			[account, name] => (account, name, "utf8"),
+			[account, name, encoding] => (account, name, encoding.as_str()),
+			_ =>
+				return Err(invalid(format!("expected 2 or 3 fields, got {}", fields.len())).into()),
+		};
+		let account = AccountId::from_ss58check(account)
+			.map_err(|e| invalid(format!("invalid account {}: {:?}", account, e)))?;
+		let username = match encoding {
+			"utf8" => name.clone().into_bytes(),
+			"hex" => from_hex(name).map_err(|e| invalid(format!("invalid hex name: {}", e)))?,
+			other => return Err(invalid(format!("unknown encoding {}", other)).into()),
+		};
+		entries.push(Entry { account, username: username.into() });
+	}
+	Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn account(seed: u8) -> AccountId {
+		AccountId::from([seed; 32])
+	}
+
+	fn parse(contents: &str) -> Result<Vec<(AccountId, Vec<u8>)>, String> {
+		parse_csv(contents)
+			.map(|entries| {
+				entries
+					.into_iter()
+					.map(|entry| (entry.account, entry.username.into_bytes()))
+					.collect()
+			})
+			.map_err(|e| e.to_string())
+	}
+
+	#[test]
+	fn csv_quoted_fields() {
+		let alice = account(1).to_ss58check();
+		let contents = format!(
+			"{}\n{alice},\"a,b\",utf8\r\n\"{bob}\",\"say \"\"hi\"\"\"\n{charlie},\"two\nlines\",utf8\n\n",
+			CSV_HEADER,
+			alice = alice,
+			bob = account(2).to_ss58check(),
+			charlie = account(3).to_ss58check(),
+		);
+
+		assert_eq!(
+			parse(&contents).unwrap(),
+			vec![
+				(account(1), b"a,b".to_vec()),
+				(account(2), b"say \"hi\"".to_vec()),
+				(account(3), b"two\nlines".to_vec()),
+			]
+		);
+	}
+
+	#[test]
+	fn csv_round_trip() {
+		let names: [&[u8]; 5] = [b"alice", b"a,b", b"say \"hi\"", b"two\nlines", &[0xff, 0x00]];
+		let entries = names
+			.iter()
+			.enumerate()
+			.map(|(i, name)| Entry { account: account(i as u8), username: name.to_vec().into() });
+		let contents = std::iter::once(CSV_HEADER.to_owned())
+			.chain(entries.map(csv_line))
+			.collect::<Vec<_>>()
+			.join("\n");
+
+		let expected: Vec<_> =
+			names.iter().enumerate().map(|(i, name)| (account(i as u8), name.to_vec())).collect();
+		assert_eq!(parse(&contents).unwrap(), expected);
+	}
+
+	fn entries(usernames: &[&str]) -> Vec<Entry> {
+		usernames
+			.iter()
+			.enumerate()
+			.map(|(i, name)| Entry {
+				account: account(i as u8),
+				username: name.as_bytes().to_vec().into(),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn patch_of_registry() {
+		let name = "a-very-long-organization-name-of-40-byte";
+		let patch = genesis_patch(Registry::Organizations, entries(&[name])).unwrap();
+
+		assert_eq!(
+			patch,
+			json!({ "organizationUsernames": { "usernames": [[account(0), name.as_bytes()]] } })
+		);
+		let patch = genesis_patch(Registry::Bots, entries(&["helperbot"])).unwrap();
+		assert!(patch.get("botUsernames").is_some());
+	}
+
+	#[test]
+	fn registry_rules_are_checked() {
+		let (long_user, long_organization) = ("a".repeat(33), "a".repeat(65));
+		let invalid = [
+			(Registry::Users, "", "must be 1 to 32 bytes long in the users registry"),
+			(Registry::Users, &long_user, "must be 1 to 32 bytes long"),
+			(Registry::Bots, "helper", "is not allowed in the bots registry"),
+			(Registry::Organizations, &long_organization, "must be 1 to 64 bytes long"),
+			(Registry::Organizations, "Acme", "is not allowed in the organizations registry"),
+			(Registry::Organizations, "acme inc", "in the organizations registry"),
+		];
+
+		for (registry, name, error) in invalid {
+			let found = genesis_patch(registry, entries(&[name])).unwrap_err();
+			assert!(found.contains(error), "{:?} does not contain {:?}", found, error);
+		}
+		assert!(genesis_patch(Registry::Users, entries(&["Acme Inc"])).is_ok());
+	}
+
+	#[test]
+	fn duplicates_are_rejected() {
+		let found = genesis_patch(Registry::Users, entries(&["alice", "alice"])).unwrap_err();
+		assert!(found.contains("listed more than once"));
+
+		let mut twice = entries(&["alice", "bob"]);
+		twice[1].account = account(0);
+		let found = genesis_patch(Registry::Users, twice).unwrap_err();
+		assert!(found.contains("more than one username"));
+	}
+
+	#[test]
+	fn csv_invalid_rows() {
+		let alice = account(1).to_ss58check();
+		let invalid = [
+			(format!("{}\n{}", CSV_HEADER, alice), "line 2: expected 2 or 3 fields, got 1"),
+			(format!("{},alice,utf8,extra", alice), "line 1: expected 2 or 3 fields, got 4"),
+			("nobody,alice".to_owned(), "line 1: invalid account nobody"),
+			(format!("{},alice,base64", alice), "line 1: unknown encoding base64"),
+			(format!("{},0xzz,hex", alice), "line 1: invalid hex name"),
+			(format!("\n{},\"alice", alice), "line 2: unterminated quote"),
+		];
+
+		for (contents, error) in invalid {
+			let found = parse(&contents).unwrap_err();
+			assert!(found.contains(error), "{:?} does not contain {:?}", found, error);
+		}
+	}
+}
@@ -0,0 +1,322 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Allowlist of the peers of a permissioned network.
+//!
+//! The pallet only keeps the list, as the bytes of libp2p peer ids, and lets
+//! `Config::AdminOrigin` change it. Nodes enforce it: while the list is not empty they only
+//! accept connections of the listed peers. An empty list leaves the network open.
+//!
+//! The list fails closed: only well-formed peer ids are accepted, and the last node cannot be
+//! removed, so a permissioned network never turns open by mistake.
+
+pub use pallet::*;
+
+#[frame_support::pallet]
+pub mod pallet {
+    use frame_support::pallet_prelude::*;
+    use frame_system::pallet_prelude::*;
+    use sp_std::vec::Vec;
+
+    /// The bytes of a libp2p peer id.
+    pub type PeerIdOf<T> = BoundedVec<u8, <T as Config>::MaxPeerIdLength>;
+
+    /// Multihash code of a peer id holding the public key itself.
+    const IDENTITY_CODE: u8 = 0x00;
+    /// Longest public key held by a peer id.
+    const MAX_INLINE_KEY_LENGTH: u8 = 42;
+    /// Multihash code of a peer id holding the SHA-256 hash of the public key.
+    const SHA2_256_CODE: u8 = 0x12;
+    /// Length of a SHA-256 hash.
+    const SHA2_256_LENGTH: u8 = 32;
+
+    /// Whether `peer_id` is the encoding of a libp2p peer id: a multihash of the public key,
+    /// either inlined or hashed with SHA-256.
+    pub fn is_valid_peer_id(peer_id: &[u8]) -> bool {
+        match peer_id {
+            [IDENTITY_CODE, length, key @ ..] => {
+                *length <= MAX_INLINE_KEY_LENGTH && key.len() == *length as usize
+            }
+            [SHA2_256_CODE, SHA2_256_LENGTH, hash @ ..] => hash.len() == SHA2_256_LENGTH as usize,
+            _ => false,
+        }
+    }
+
+    #[pallet::config]
+    pub trait Config: frame_system::Config {
+        /// The overarching event type.
+        type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+        /// Origin allowed to add and remove nodes
+        type AdminOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+        /// Maximum number of allowed nodes
+        #[pallet::constant]
+        type MaxNodes: Get<u32>;
+        /// Maximum length of a peer id, in bytes
+        #[pallet::constant]
+        type MaxPeerIdLength: Get<u32>;
+    }
+
+    #[pallet::pallet]
+    pub struct Pallet<T>(_);
+
+    /// Peer ids of the nodes allowed on the network. Empty when the network is open.
+    #[pallet::storage]
+    pub type AllowedNodes<T: Config> =
+        StorageValue<_, BoundedBTreeSet<PeerIdOf<T>, T::MaxNodes>, ValueQuery>;
+
+    #[pallet::genesis_config]
+    #[derive(frame_support::DefaultNoBound)]
+    pub struct GenesisConfig<T: Config> {
+        /// Peer ids allowed from genesis
+        pub nodes: Vec<Vec<u8>>,
+        #[serde(skip)]
+        pub _config: PhantomData<T>,
+    }
+
+    #[pallet::genesis_build]
+    impl<T: Config> BuildGenesisConfig for GenesisConfig<T> {
+        fn build(&self) {
+            for peer_id in &self.nodes {
+                Pallet::<T>::do_add_node(peer_id.clone())
+                    .expect("Genesis nodes are valid and unique");
+            }
+        }
+    }
+
+    #[pallet::event]
+    #[pallet::generate_deposit(pub(super) fn deposit_event)]
+    pub enum Event<T: Config> {
+        /// Node was added to the allowlist
+        NodeAdded {
+            /// The peer id of the node
+            peer_id: PeerIdOf<T>,
+        },
+        /// Node was removed from the allowlist
+        NodeRemoved {
+            /// The peer id of the node
+            peer_id: PeerIdOf<T>,
+        },
+    }
+
+    #[pallet::error]
+    pub enum Error<T> {
+        /// Peer id is longer than `MaxPeerIdLength`
+        PeerIdTooLong,
+        /// Node is already allowed
+        AlreadyAllowed,
+        /// Node is not allowed
+        NotAllowed,
+        /// The allowlist is full
+        TooManyNodes,
+        /// Peer id is not the encoding of a libp2p peer id
+        InvalidPeerId,
+        /// The last node cannot be removed, which would open the network
+        LastNode,
+    }
+
+    #[pallet::call]
+    impl<T: Config> Pallet<T> {
+        /// Allow the node with peer id `peer_id`.
+        ///
+        /// Adding the first node turns the network into a permissioned one.
+        #[pallet::call_index(0)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(1, 1))]
+        pub fn add_node(origin: OriginFor<T>, peer_id: Vec<u8>) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let peer_id = Self::do_add_node(peer_id)?;
+            Self::deposit_event(Event::NodeAdded { peer_id });
+
+            Ok(())
+        }
+
+        /// Remove the node with peer id `peer_id` from the allowlist.
+        ///
+        /// The last node cannot be removed, as that would open the network to every peer.
+        #[pallet::call_index(1)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(1, 1))]
+        pub fn remove_node(origin: OriginFor<T>, peer_id: Vec<u8>) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let peer_id: PeerIdOf<T> = peer_id.try_into().map_err(|_| Error::<T>::NotAllowed)?;
+
+            AllowedNodes::<T>::try_mutate(|nodes| {
+                ensure!(nodes.contains(&peer_id), Error::<T>::NotAllowed);
+                ensure!(nodes.len() > 1, Error::<T>::LastNode);
+                nodes.remove(&peer_id);
+                Ok::<_, Error<T>>(())
+            })?;
+            Self::deposit_event(Event::NodeRemoved { peer_id });
+
+            Ok(())
+        }
+    }
+
+    impl<T: Config> Pallet<T> {
+        fn do_add_node(peer_id: Vec<u8>) -> Result<PeerIdOf<T>, Error<T>> {
+            let peer_id: PeerIdOf<T> = peer_id.try_into().map_err(|_| Error::<T>::PeerIdTooLong)?;
+            ensure!(is_valid_peer_id(&peer_id), Error::<T>::InvalidPeerId);
+
+            AllowedNodes::<T>::try_mutate(|nodes| {
+                ensure!(!nodes.contains(&peer_id), Error::<T>::AlreadyAllowed);
+                nodes
+                    .try_insert(peer_id.clone())
+                    .map_err(|_| Error::<T>::TooManyNodes)?;
+                Ok(peer_id)
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_support::{assert_noop, assert_ok, derive_impl, traits::ConstU32, BoundedVec};
+    use frame_system::EnsureRoot;
+    use sp_runtime::{BuildStorage, DispatchError};
+
+    type Block = frame_system::mocking::MockBlock<Test>;
+
+    #[frame_support::runtime]
+    mod runtime {
+        #[runtime::runtime]
+        #[runtime::derive(
+            RuntimeCall,
+            RuntimeEvent,
+            RuntimeError,
+            RuntimeOrigin,
+            RuntimeFreezeReason,
+            RuntimeHoldReason,
+            RuntimeSlashReason,
+            RuntimeLockId,
+            RuntimeTask
+        )]
+        pub struct Test;
+
+        #[runtime::pallet_index(0)]
+        pub type System = frame_system;
+
+        #[runtime::pallet_index(1)]
+        pub type NodeAllowlist = crate::pallet;
+    }
+
+    #[derive_impl(frame_system::config_preludes::TestDefaultConfig)]
+    impl frame_system::Config for Test {
+        type Block = Block;
+    }
+
+    impl Config for Test {
+        type RuntimeEvent = RuntimeEvent;
+        type AdminOrigin = EnsureRoot<u64>;
+        type MaxNodes = ConstU32<2>;
+        type MaxPeerIdLength = ConstU32<34>;
+    }
+
+    /// A peer id holding the SHA-256 hash `[n; 32]`.
+    fn peer_id(n: u8) -> Vec<u8> {
+        let mut peer_id = vec![0x12, 32];
+        peer_id.extend([n; 32]);
+        peer_id
+    }
+
+    fn new_test_ext() -> sp_io::TestExternalities {
+        let mut t = frame_system::GenesisConfig::<Test>::default()
+            .build_storage()
+            .unwrap();
+        GenesisConfig::<Test> {
+            nodes: vec![peer_id(1)],
+            ..Default::default()
+        }
+        .assimilate_storage(&mut t)
+        .unwrap();
+        let mut ext = sp_io::TestExternalities::new(t);
+        ext.execute_with(|| System::set_block_number(1));
+        ext
+    }
+
+    fn allowed() -> Vec<Vec<u8>> {
+        AllowedNodes::<Test>::get()
+            .into_iter()
+            .map(|peer_id| peer_id.into_inner())
+            .collect()
+    }
+
+    #[test]
+    fn test_add_node() {
+        new_test_ext().execute_with(|| {
+            assert_eq!(allowed(), vec![peer_id(1)]);
+            assert_noop!(
+                NodeAllowlist::add_node(RuntimeOrigin::signed(1), peer_id(2)),
+                DispatchError::BadOrigin
+            );
+            assert_noop!(
+                NodeAllowlist::add_node(RuntimeOrigin::root(), peer_id(1)),
+                Error::<Test>::AlreadyAllowed
+            );
+            assert_noop!(
+                NodeAllowlist::add_node(RuntimeOrigin::root(), [peer_id(2), vec![0]].concat()),
+                Error::<Test>::PeerIdTooLong
+            );
+            assert_noop!(
+                NodeAllowlist::add_node(RuntimeOrigin::root(), b"node-b".to_vec()),
+                Error::<Test>::InvalidPeerId
+            );
+
+            assert_ok!(NodeAllowlist::add_node(RuntimeOrigin::root(), peer_id(2)));
+            System::assert_last_event(
+                Event::NodeAdded {
+                    peer_id: BoundedVec::truncate_from(peer_id(2)),
+                }
+                .into(),
+            );
+            assert_eq!(allowed(), vec![peer_id(1), peer_id(2)]);
+
+            assert_noop!(
+                NodeAllowlist::add_node(RuntimeOrigin::root(), peer_id(3)),
+                Error::<Test>::TooManyNodes
+            );
+        });
+    }
+
+    #[test]
+    fn test_remove_node() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                NodeAllowlist::remove_node(RuntimeOrigin::root(), peer_id(2)),
+                Error::<Test>::NotAllowed
+            );
+            // The network stays permissioned
+            assert_noop!(
+                NodeAllowlist::remove_node(RuntimeOrigin::root(), peer_id(1)),
+                Error::<Test>::LastNode
+            );
+
+            assert_ok!(NodeAllowlist::add_node(RuntimeOrigin::root(), peer_id(2)));
+            assert_ok!(NodeAllowlist::remove_node(
+                RuntimeOrigin::root(),
+                peer_id(1)
+            ));
+            System::assert_last_event(
+                Event::NodeRemoved {
+                    peer_id: BoundedVec::truncate_from(peer_id(1)),
+                }
+                .into(),
+            );
+            assert_eq!(allowed(), vec![peer_id(2)]);
+        });
+    }
+
+    #[test]
+    fn test_valid_peer_id() {
+        // Ed25519 key inlined in the peer id
+        let mut inlined = vec![0x00, 36, 0x08, 0x01, 0x12, 0x20];
+        inlined.extend([7; 32]);
+        assert!(is_valid_peer_id(&inlined));
+        assert!(is_valid_peer_id(&peer_id(1)));
+
+        assert!(!is_valid_peer_id(b""));
+        assert!(!is_valid_peer_id(&inlined[..inlined.len() - 1]));
+        assert!(!is_valid_peer_id(&[&[0x00, 43][..], &[0; 43]].concat()));
+        assert!(!is_valid_peer_id(&peer_id(1)[..33]));
+        assert!(!is_valid_peer_id(&[peer_id(1), vec![0]].concat()));
+        // Multihash codes other than identity and SHA-256
+        assert!(!is_valid_peer_id(&[&[0x13, 32][..], &[0; 32]].concat()));
+    }
+}
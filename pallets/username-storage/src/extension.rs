@@ -0,0 +1,244 @@
+use crate::{Call, Config, Error, Pallet};
+use codec::{Decode, DecodeWithMemTracking, Encode, HasCompact};
+use frame_support::{
+    dispatch::DispatchInfo,
+    pallet_prelude::*,
+    traits::{IsSubType, PalletInfoAccess},
+};
+use scale_info::TypeInfo;
+use sp_runtime::{
+    impl_tx_ext_default,
+    traits::{
+        AsSystemOriginSigner, DispatchInfoOf, DispatchOriginOf, Dispatchable, Implication,
+        TransactionExtension, ValidateResult,
+    },
+    transaction_validity::{
+        InvalidTransaction, TransactionSource, TransactionValidityError, ValidTransaction,
+    },
+};
+use sp_std::marker::PhantomData;
+
+/// Pool and block level checks for sponsored, fee-less first registrations.
+///
+/// The fee itself is waived by `pallet_skip_feeless_payment::SkipCheckIfFeeless` through the
+/// `feeless_if` condition of `set_username`. This extension makes sure a sponsored registration
+/// only enters the pool once per account and that no more than `MaxSponsoredPerBlock` of them
+/// are included in a block. Transactions over the block budget are reported as exhausting
+/// resources, so the block author keeps them in the pool for a later block.
+///
+/// Only the fee is waived, the sender must still exist on chain. Sponsored registrations go
+/// through `CheckNonce`, which rejects accounts without providers, so an account with no balance
+/// at all needs to receive the existential deposit before it can register. Nothing pays the fee
+/// on its behalf, neither a sponsor account nor a treasury.
+#[derive(Encode, Decode, DecodeWithMemTracking, Clone, Eq, PartialEq, TypeInfo)]
+#[scale_info(skip_type_params(T, I))]
+pub struct CheckSponsoredRegistration<T: Config<I> + Send + Sync, I: 'static + Send + Sync = ()>(
+    PhantomData<(T, I)>,
+);
+
+impl<T: Config<I> + Send + Sync, I: 'static + Send + Sync> CheckSponsoredRegistration<T, I> {
+    /// Create new `TransactionExtension` to check sponsored registrations.
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Config<I> + Send + Sync, I: 'static + Send + Sync> Default
+    for CheckSponsoredRegistration<T, I>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Config<I> + Send + Sync, I: 'static + Send + Sync> core::fmt::Debug
+    for CheckSponsoredRegistration<T, I>
+{
+    #[cfg(feature = "std")]
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "CheckSponsoredRegistration")
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn fmt(&self, _: &mut core::fmt::Formatter) -> core::fmt::Result {
+        Ok(())
+    }
+}
+
+impl<T: Config<I> + Send + Sync, I: 'static + Send + Sync> TransactionExtension<T::RuntimeCall>
+    for CheckSponsoredRegistration<T, I>
+where
+    T::RuntimeCall: Dispatchable<Info = DispatchInfo> + IsSubType<Call<T, I>>,
+    <T::RuntimeCall as Dispatchable>::RuntimeOrigin: AsSystemOriginSigner<T::AccountId> + Clone,
+{
+    const IDENTIFIER: &'static str = "CheckSponsoredRegistration";
+    type Implicit = ();
+    type Val = ();
+    type Pre = ();
+
+    fn weight(&self, call: &T::RuntimeCall) -> Weight {
+        match call.is_sub_type() {
+            // `Usernames`, `SponsoredAccounts` and `SponsoredInBlock`, and `BlockedUsernames`,
+            // `UsernameOwners` and `ReservedUsernames` to validate the username
+            Some(Call::set_username { .. }) => T::DbWeight::get().reads(6),
+            _ => Weight::zero(),
+        }
+    }
+
+    fn validate(
+        &self,
+        origin: DispatchOriginOf<T::RuntimeCall>,
+        call: &T::RuntimeCall,
+        _info: &DispatchInfoOf<T::RuntimeCall>,
+        _len: usize,
+        _self_implicit: Self::Implicit,
+        _inherited_implication: &impl Implication,
+        source: TransactionSource,
+    ) -> ValidateResult<Self::Val, T::RuntimeCall> {
+        let Some(who) = origin.as_system_origin_signer() else {
+            return Ok((ValidTransaction::default(), (), origin));
+        };
+        let Some(Call::set_username { username }) = call.is_sub_type() else {
+            return Ok((ValidTransaction::default(), (), origin));
+        };
+        if !Pallet::<T, I>::is_sponsorable(who, username) {
+            return Ok((ValidTransaction::default(), (), origin));
+        }
+
+        if source == TransactionSource::InBlock
+            && Pallet::<T, I>::sponsored_in_block() >= T::MaxSponsoredPerBlock::get()
+        {
+            return Err(InvalidTransaction::ExhaustsResources.into());
+        }
+
+        let validity = ValidTransaction::with_tag_prefix("SponsoredRegistration")
+            .and_provides(who)
+            .build()?;
+
+        Ok((validity, (), origin))
+    }
+
+    impl_tx_ext_default!(T::RuntimeCall; prepare);
+}
+
+/// Pool and block level checks for `set_username`.
+///
+/// Without it, an empty, too long, blocked or taken username is only rejected at dispatch, once
+/// the transaction has taken block space and paid its fee. This extension runs the checks of
+/// [`Pallet::validate_username`] when the transaction is validated instead, and reports a failure
+/// as `InvalidTransaction::Custom` with the index of the pallet error.
+///
+/// Valid `set_username` transactions provide the `(account, "username", nonce)` tag, prefixed by the
+/// pallet name so that registries do not clash, where `nonce` is the nonce of the transaction.
+/// Changes of an account at different nonces do not clash and are applied in nonce order, as
+/// `CheckNonce` requires. A change signed again at the nonce of a pending one replaces it when its
+/// priority is higher, for example with a higher tip, and is rejected by the pool otherwise.
+///
+/// The nonce is read from the explicit data of `CheckNonce`, so this extension must come right
+/// before `CheckNonce` in the transaction extensions of the runtime.
+#[derive(Encode, Decode, DecodeWithMemTracking, Clone, Eq, PartialEq, TypeInfo)]
+#[scale_info(skip_type_params(T, I))]
+pub struct CheckUsername<T: Config<I> + Send + Sync, I: 'static + Send + Sync = ()>(
+    PhantomData<(T, I)>,
+);
+
+impl<T: Config<I> + Send + Sync, I: 'static + Send + Sync> CheckUsername<T, I> {
+    /// Create new `TransactionExtension` to check usernames.
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Config<I> + Send + Sync, I: 'static + Send + Sync> Default for CheckUsername<T, I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Config<I> + Send + Sync, I: 'static + Send + Sync> core::fmt::Debug
+    for CheckUsername<T, I>
+{
+    #[cfg(feature = "std")]
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "CheckUsername")
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn fmt(&self, _: &mut core::fmt::Formatter) -> core::fmt::Result {
+        Ok(())
+    }
+}
+
+impl<T: Config<I> + Send + Sync, I: 'static + Send + Sync> TransactionExtension<T::RuntimeCall>
+    for CheckUsername<T, I>
+where
+    T::RuntimeCall: Dispatchable<Info = DispatchInfo> + IsSubType<Call<T, I>>,
+    <T::RuntimeCall as Dispatchable>::RuntimeOrigin: AsSystemOriginSigner<T::AccountId> + Clone,
+{
+    const IDENTIFIER: &'static str = "CheckUsername";
+    type Implicit = ();
+    type Val = ();
+    type Pre = ();
+
+    fn weight(&self, call: &T::RuntimeCall) -> Weight {
+        match call.is_sub_type() {
+            // `BlockedUsernames`, `UsernameOwners` and `ReservedUsernames`
+            Some(Call::set_username { .. }) => T::DbWeight::get().reads(3),
+            _ => Weight::zero(),
+        }
+    }
+
+    fn validate(
+        &self,
+        origin: DispatchOriginOf<T::RuntimeCall>,
+        call: &T::RuntimeCall,
+        _info: &DispatchInfoOf<T::RuntimeCall>,
+        _len: usize,
+        _self_implicit: Self::Implicit,
+        inherited_implication: &impl Implication,
+        _source: TransactionSource,
+    ) -> ValidateResult<Self::Val, T::RuntimeCall> {
+        let Some(who) = origin.as_system_origin_signer() else {
+            return Ok((ValidTransaction::default(), (), origin));
+        };
+        let Some(Call::set_username { username }) = call.is_sub_type() else {
+            return Ok((ValidTransaction::default(), (), origin));
+        };
+
+        Pallet::<T, I>::validate_username(who, username.clone()).map_err(invalid::<T, I>)?;
+
+        let nonce =
+            transaction_nonce::<T>(call, inherited_implication).ok_or(InvalidTransaction::Call)?;
+        let validity = ValidTransaction::with_tag_prefix(Pallet::<T, I>::name())
+            .and_provides((who, b"username", nonce))
+            .build()?;
+
+        Ok((validity, (), origin))
+    }
+
+    impl_tx_ext_default!(T::RuntimeCall; prepare);
+}
+
+/// The nonce of the transaction of `call`, given the implication of an extension that comes right
+/// before `CheckNonce`.
+///
+/// The implication starts with the encoded call, followed by the explicit data of the extensions
+/// that come next, which for `CheckNonce` is its compact nonce.
+fn transaction_nonce<T: frame_system::Config>(
+    call: &T::RuntimeCall,
+    implication: &impl Implication,
+) -> Option<T::Nonce> {
+    implication.using_encoded(|data| {
+        let mut data = data.get(call.encoded_size()..)?;
+        <<T::Nonce as HasCompact>::Type as Decode>::decode(&mut data)
+            .ok()
+            .map(Into::into)
+    })
+}
+
+/// The transaction validity error reporting `error`.
+fn invalid<T: Config<I>, I: 'static>(error: Error<T, I>) -> TransactionValidityError {
+    // Pallet errors are fieldless, their encoding is their index
+    let index = error.encode().first().copied().unwrap_or_default();
+    InvalidTransaction::Custom(index).into()
+}
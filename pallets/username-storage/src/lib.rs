@@ -2,39 +2,181 @@
 
 pub use pallet::*;
 
+mod extension;
+mod lookup;
+pub mod migrations;
+mod nonfungibles;
+pub mod traits;
+pub use extension::{CheckSponsoredRegistration, CheckUsername};
+pub use lookup::UsernameLookup;
+pub use traits::{OnUsernameChanged, UsernameProvider, UsernameRules};
+
 #[frame_support::pallet]
 pub mod pallet {
     use super::*;
-    use frame_support::{pallet_prelude::*, traits::Get};
+    use frame_support::{
+        pallet_prelude::*,
+        traits::{
+            fungible::{Inspect, Mutate},
+            schedule::{
+                v3::{Named as ScheduleNamed, TaskName},
+                DispatchTime, HARD_DEADLINE,
+            },
+            tokens::Preservation,
+            Bounded, Get, PalletInfoAccess,
+        },
+    };
     use frame_system::pallet_prelude::*;
     use sp_api::decl_runtime_apis;
+    use sp_runtime::traits::StaticLookup;
     use sp_std::{convert::TryInto, vec::Vec};
 
+    type AccountIdLookupOf<T> = <<T as frame_system::Config>::Lookup as StaticLookup>::Source;
+    pub type BalanceOf<T, I = ()> =
+        <<T as Config<I>>::Currency as Inspect<<T as frame_system::Config>::AccountId>>::Balance;
+
     #[pallet::config]
-    pub trait Config: frame_system::Config {
+    pub trait Config<I: 'static = ()>:
+        frame_system::Config<RuntimeCall: From<Call<Self, I>>>
+    {
         /// The overarching event type.
-        type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+        type RuntimeEvent: From<Event<Self, I>>
+            + IsType<<Self as frame_system::Config>::RuntimeEvent>;
         /// Maximum length of username
         #[pallet::constant]
         type MaxUsernameLength: Get<u32>;
+        /// Rules a username must follow, on top of its length
+        type UsernameRules: UsernameRules;
+        /// Handler called whenever the username of an account changes
+        type OnUsernameChanged: OnUsernameChanged<Self::AccountId>;
+        /// Maximum number of fee-less first registrations included in a single block.
+        /// Zero disables sponsored registrations.
+        #[pallet::constant]
+        type MaxSponsoredPerBlock: Get<u32>;
+        /// Currency used to pay for usernames bought on the marketplace
+        type Currency: Mutate<Self::AccountId>;
+        /// Origin allowed to moderate usernames: force clear them and manage the blocklist
+        type AdminOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+        /// The caller origin of the scheduled username changes
+        type PalletsOrigin: From<frame_system::RawOrigin<Self::AccountId>>;
+        /// Scheduler applying the username changes at their block
+        type Scheduler: ScheduleNamed<
+            BlockNumberFor<Self>,
+            <Self as frame_system::Config>::RuntimeCall,
+            Self::PalletsOrigin,
+        >;
+        /// Maximum number of blocks between scheduling a username change and applying it
+        #[pallet::constant]
+        type MaxChangeDelay: Get<BlockNumberFor<Self>>;
     }
 
+    /// The in-code storage version.
+    const STORAGE_VERSION: StorageVersion = StorageVersion::new(2);
+
     #[pallet::pallet]
-    pub struct Pallet<T>(_);
+    #[pallet::storage_version(STORAGE_VERSION)]
+    pub struct Pallet<T, I = ()>(_);
 
     #[pallet::storage]
     #[pallet::getter(fn usernames)]
-    pub type Usernames<T: Config> = StorageMap<
+    pub type Usernames<T: Config<I>, I: 'static = ()> = StorageMap<
+        _,
+        Blake2_128Concat,
+        T::AccountId,
+        BoundedVec<u8, T::MaxUsernameLength>,
+        OptionQuery,
+    >;
+
+    /// Reverse index of `Usernames`, from a username to the account that owns it.
+    #[pallet::storage]
+    pub type UsernameOwners<T: Config<I>, I: 'static = ()> = StorageMap<
+        _,
+        Blake2_128Concat,
+        BoundedVec<u8, T::MaxUsernameLength>,
+        T::AccountId,
+        OptionQuery,
+    >;
+
+    /// Number of accounts with a username.
+    #[pallet::storage]
+    pub type UsernameCount<T: Config<I>, I: 'static = ()> = StorageValue<_, u32, ValueQuery>;
+
+    /// Usernames listed for sale, with their asking price. The seller is the current owner.
+    #[pallet::storage]
+    pub type Listings<T: Config<I>, I: 'static = ()> = StorageMap<
+        _,
+        Blake2_128Concat,
+        BoundedVec<u8, T::MaxUsernameLength>,
+        BalanceOf<T, I>,
+        OptionQuery,
+    >;
+
+    /// Usernames that cannot be registered.
+    #[pallet::storage]
+    pub type BlockedUsernames<T: Config<I>, I: 'static = ()> =
+        StorageMap<_, Blake2_128Concat, BoundedVec<u8, T::MaxUsernameLength>, (), OptionQuery>;
+
+    /// Username changes scheduled by accounts, with the block they take effect at.
+    #[pallet::storage]
+    pub type PendingChanges<T: Config<I>, I: 'static = ()> = StorageMap<
         _,
         Blake2_128Concat,
         T::AccountId,
+        (BoundedVec<u8, T::MaxUsernameLength>, BlockNumberFor<T>),
+        OptionQuery,
+    >;
+
+    /// Usernames reserved by a scheduled change, with the account they are reserved for.
+    #[pallet::storage]
+    pub type ReservedUsernames<T: Config<I>, I: 'static = ()> = StorageMap<
+        _,
+        Blake2_128Concat,
         BoundedVec<u8, T::MaxUsernameLength>,
+        T::AccountId,
         OptionQuery,
     >;
 
+    /// Accounts that already used their sponsored first registration.
+    #[pallet::storage]
+    pub type SponsoredAccounts<T: Config<I>, I: 'static = ()> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, bool, ValueQuery>;
+
+    /// Number of sponsored registrations included so far, along with the block they belong to.
+    #[pallet::storage]
+    pub type SponsoredInBlock<T: Config<I>, I: 'static = ()> =
+        StorageValue<_, (BlockNumberFor<T>, u32), ValueQuery>;
+
+    #[pallet::genesis_config]
+    #[derive(frame_support::DefaultNoBound)]
+    pub struct GenesisConfig<T: Config<I>, I: 'static = ()> {
+        /// Usernames registered at genesis, by account
+        pub usernames: Vec<(T::AccountId, Vec<u8>)>,
+        #[serde(skip)]
+        pub _config: PhantomData<I>,
+    }
+
+    #[pallet::genesis_build]
+    impl<T: Config<I>, I: 'static> BuildGenesisConfig for GenesisConfig<T, I> {
+        fn build(&self) {
+            for (who, username) in &self.usernames {
+                assert!(
+                    !Usernames::<T, I>::contains_key(who),
+                    "Account has more than one genesis username"
+                );
+                let username = Pallet::<T, I>::validate_username(who, username.clone())
+                    .expect("Genesis usernames are valid and unique");
+
+                Usernames::<T, I>::insert(who, &username);
+                UsernameOwners::<T, I>::insert(&username, who);
+                UsernameCount::<T, I>::mutate(|count| *count = count.saturating_add(1));
+                T::OnUsernameChanged::on_username_changed(who, None, Some(&username[..]));
+            }
+        }
+    }
+
     #[pallet::event]
     #[pallet::generate_deposit(pub(super) fn deposit_event)]
-    pub enum Event<T: Config> {
+    pub enum Event<T: Config<I>, I: 'static = ()> {
         /// Username was set for an account
         UsernameSet {
             /// The account that set the username
@@ -58,61 +200,739 @@ pub mod pallet {
             /// The username that was retrieved
             username: BoundedVec<u8, T::MaxUsernameLength>,
         },
+        /// Username was cleared for an account
+        UsernameCleared {
+            /// The account that cleared its username
+            who: T::AccountId,
+        },
+        /// The first username registration of an account was sponsored
+        RegistrationSponsored {
+            /// The account whose registration fee was waived
+            who: T::AccountId,
+        },
+        /// Username was transferred to another account
+        UsernameTransferred {
+            /// The previous owner of the username
+            from: T::AccountId,
+            /// The new owner of the username
+            to: T::AccountId,
+            /// The username that was transferred
+            username: BoundedVec<u8, T::MaxUsernameLength>,
+        },
+        /// Username was listed for sale
+        UsernameListed {
+            /// The owner of the username
+            who: T::AccountId,
+            /// The username that was listed
+            username: BoundedVec<u8, T::MaxUsernameLength>,
+            /// The asking price
+            price: BalanceOf<T, I>,
+        },
+        /// Username was removed from sale by its owner
+        UsernameDelisted {
+            /// The owner of the username
+            who: T::AccountId,
+            /// The username that was delisted
+            username: BoundedVec<u8, T::MaxUsernameLength>,
+        },
+        /// Listed username was bought
+        UsernameSold {
+            /// The previous owner of the username
+            seller: T::AccountId,
+            /// The new owner of the username
+            buyer: T::AccountId,
+            /// The username that was sold
+            username: BoundedVec<u8, T::MaxUsernameLength>,
+            /// The price paid to the seller
+            price: BalanceOf<T, I>,
+        },
+        /// Username was cleared by the admin origin
+        UsernameForceCleared {
+            /// The account whose username was cleared
+            who: T::AccountId,
+            /// The username that was cleared
+            username: BoundedVec<u8, T::MaxUsernameLength>,
+        },
+        /// Username was added to the blocklist
+        UsernameBlocked {
+            /// The username that was blocked
+            username: BoundedVec<u8, T::MaxUsernameLength>,
+        },
+        /// Username was removed from the blocklist
+        UsernameUnblocked {
+            /// The username that was unblocked
+            username: BoundedVec<u8, T::MaxUsernameLength>,
+        },
+        /// Username change was scheduled, the username is reserved until then
+        UsernameChangeScheduled {
+            /// The account changing its username
+            who: T::AccountId,
+            /// The new username
+            username: BoundedVec<u8, T::MaxUsernameLength>,
+            /// The block the change takes effect at
+            at: BlockNumberFor<T>,
+        },
+        /// Scheduled username change was cancelled, the username is released
+        UsernameChangeCancelled {
+            /// The account that scheduled the change
+            who: T::AccountId,
+            /// The username that was reserved
+            username: BoundedVec<u8, T::MaxUsernameLength>,
+        },
     }
 
     #[pallet::error]
-    pub enum Error<T> {
+    pub enum Error<T, I = ()> {
         /// Username is too long
         UsernameTooLong,
         /// Username is empty
         UsernameEmpty,
+        /// Account has no username
+        NoUsername,
+        /// Username is already owned by another account
+        UsernameTaken,
+        /// Destination account already has a username
+        DestinationHasUsername,
+        /// Username is not listed for sale
+        NotListed,
+        /// Account cannot buy its own username
+        CannotBuyOwnUsername,
+        /// Username is on the blocklist
+        UsernameBlocked,
+        /// Username is already on the blocklist
+        AlreadyBlocked,
+        /// Username is not on the blocklist
+        NotBlocked,
+        /// Username is reserved by a scheduled change of another account
+        UsernameReserved,
+        /// Account already has a scheduled username change
+        ChangeAlreadyScheduled,
+        /// Scheduled change block is not in the future
+        ChangeNotInFuture,
+        /// Scheduled change block is more than `MaxChangeDelay` blocks ahead
+        ChangeTooFarAhead,
+        /// Account has no scheduled username change
+        NoPendingChange,
+        /// Scheduled username change is not due yet
+        ChangeNotDue,
+        /// Username does not follow the rules of the registry
+        UsernameNotAllowed,
+        /// Asking price is above the maximum price of the buyer
+        PriceTooHigh,
+    }
+
+    #[pallet::hooks]
+    impl<T: Config<I>, I: 'static> Hooks<BlockNumberFor<T>> for Pallet<T, I> {
+        #[cfg(feature = "try-runtime")]
+        fn try_state(_n: BlockNumberFor<T>) -> Result<(), sp_runtime::TryRuntimeError> {
+            Self::do_try_state()
+        }
     }
 
     #[pallet::call]
-    impl<T: Config> Pallet<T> {
+    impl<T: Config<I>, I: 'static> Pallet<T, I> {
+        /// Set the username of the signing account.
+        ///
+        /// The first registration of an account is fee-less when it is eligible for sponsoring,
+        /// see [`Pallet::is_sponsorable`].
         #[pallet::call_index(0)]
         #[pallet::weight(Weight::default())]
+        #[pallet::feeless_if(|origin: &OriginFor<T>, username: &Vec<u8>| -> bool {
+            ensure_signed(origin.clone())
+                .map(|who| Pallet::<T, I>::is_sponsorable(&who, username))
+                .unwrap_or(false)
+        })]
         pub fn set_username(origin: OriginFor<T>, username: Vec<u8>) -> DispatchResult {
             let who = ensure_signed(origin)?;
+            let sponsored = Self::is_sponsorable(&who, &username);
+
+            let bounded_username = Self::validate_username(&who, username)?;
+            Self::do_set_username(&who, bounded_username);
+
+            if sponsored {
+                Self::note_sponsored(&who);
+            }
+
+            Ok(())
+        }
+
+        #[pallet::call_index(1)]
+        // Reads `Usernames` and `UsernameCount`, writes them, `UsernameOwners` and `Listings`
+        #[pallet::weight(T::DbWeight::get().reads_writes(2, 4))]
+        pub fn clear_username(origin: OriginFor<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            Self::remove_username(&who)?;
+            Self::deposit_event(Event::UsernameCleared { who });
+
+            Ok(())
+        }
+
+        /// Transfer the username of the signing account to `dest`, which must not have one.
+        #[pallet::call_index(2)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(2, 3))]
+        pub fn transfer_username(
+            origin: OriginFor<T>,
+            dest: AccountIdLookupOf<T>,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            let dest = T::Lookup::lookup(dest)?;
+
+            Self::do_transfer(&who, &dest)
+        }
+
+        /// List the username of the signing account for sale at `price`.
+        ///
+        /// Listing an already listed username updates its price.
+        #[pallet::call_index(3)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(1, 1))]
+        pub fn list_username(origin: OriginFor<T>, price: BalanceOf<T, I>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            let username = Usernames::<T, I>::get(&who).ok_or(Error::<T, I>::NoUsername)?;
+
+            Listings::<T, I>::insert(&username, price);
+            Self::deposit_event(Event::UsernameListed {
+                who,
+                username,
+                price,
+            });
+
+            Ok(())
+        }
+
+        /// Buy a listed username, paying its asking price to the current owner.
+        ///
+        /// The buyer must not have a username. The purchase fails when the asking price is above
+        /// `max_price`, so that the owner cannot raise it while the purchase is pending.
+        #[pallet::call_index(4)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(5, 6))]
+        pub fn buy_username(
+            origin: OriginFor<T>,
+            username: Vec<u8>,
+            max_price: BalanceOf<T, I>,
+        ) -> DispatchResult {
+            let buyer = ensure_signed(origin)?;
+            let username: BoundedVec<u8, T::MaxUsernameLength> = username
+                .try_into()
+                .map_err(|_| Error::<T, I>::UsernameTooLong)?;
+
+            let price = Listings::<T, I>::get(&username).ok_or(Error::<T, I>::NotListed)?;
+            ensure!(price <= max_price, Error::<T, I>::PriceTooHigh);
+            let seller = UsernameOwners::<T, I>::get(&username).ok_or(Error::<T, I>::NotListed)?;
+            ensure!(seller != buyer, Error::<T, I>::CannotBuyOwnUsername);
+
+            T::Currency::transfer(&buyer, &seller, price, Preservation::Preserve)?;
+            Self::do_transfer(&seller, &buyer)?;
+
+            Self::deposit_event(Event::UsernameSold {
+                seller,
+                buyer,
+                username,
+                price,
+            });
+
+            Ok(())
+        }
+
+        /// Remove the username of the signing account from sale.
+        #[pallet::call_index(5)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(2, 1))]
+        pub fn delist_username(origin: OriginFor<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            let username = Usernames::<T, I>::get(&who).ok_or(Error::<T, I>::NoUsername)?;
+
+            Listings::<T, I>::take(&username).ok_or(Error::<T, I>::NotListed)?;
+            Self::deposit_event(Event::UsernameDelisted { who, username });
+
+            Ok(())
+        }
+
+        /// Clear the username of `who`, and cancel its scheduled username change.
+        #[pallet::call_index(6)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(3, 7))]
+        pub fn force_clear_username(
+            origin: OriginFor<T>,
+            who: AccountIdLookupOf<T>,
+        ) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let who = T::Lookup::lookup(who)?;
+
+            Self::cancel_change(&who);
+            let username = Self::remove_username(&who)?;
+            Self::deposit_event(Event::UsernameForceCleared { who, username });
+
+            Ok(())
+        }
+
+        /// Prevent `username` from being registered.
+        ///
+        /// A current owner keeps the username until it is cleared, see
+        /// [`Pallet::force_clear_username`]. A scheduled change to `username` is cancelled.
+        #[pallet::call_index(7)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(3, 4))]
+        pub fn block_username(origin: OriginFor<T>, username: Vec<u8>) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+            ensure!(!username.is_empty(), Error::<T, I>::UsernameEmpty);
+            let username: BoundedVec<_, _> = username
+                .try_into()
+                .map_err(|_| Error::<T, I>::UsernameTooLong)?;
+
+            ensure!(
+                !BlockedUsernames::<T, I>::contains_key(&username),
+                Error::<T, I>::AlreadyBlocked
+            );
+            if let Some(holder) = ReservedUsernames::<T, I>::get(&username) {
+                Self::cancel_change(&holder);
+            }
+            BlockedUsernames::<T, I>::insert(&username, ());
+            Self::deposit_event(Event::UsernameBlocked { username });
+
+            Ok(())
+        }
+
+        /// Allow `username` to be registered again.
+        #[pallet::call_index(8)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(1, 1))]
+        pub fn unblock_username(origin: OriginFor<T>, username: Vec<u8>) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let username: BoundedVec<_, _> =
+                username.try_into().map_err(|_| Error::<T, I>::NotBlocked)?;
+
+            BlockedUsernames::<T, I>::take(&username).ok_or(Error::<T, I>::NotBlocked)?;
+            Self::deposit_event(Event::UsernameUnblocked { username });
+
+            Ok(())
+        }
+
+        /// Schedule the username of the signing account to change to `username` at block `at`.
+        ///
+        /// `username` is reserved for the signing account until the change is applied or
+        /// cancelled with [`Pallet::cancel_username_change`]. An account has at most one scheduled
+        /// change.
+        #[pallet::call_index(9)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(5, 4))]
+        pub fn schedule_username_change(
+            origin: OriginFor<T>,
+            username: Vec<u8>,
+            at: BlockNumberFor<T>,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            let username = Self::validate_username(&who, username)?;
+            ensure!(
+                !PendingChanges::<T, I>::contains_key(&who),
+                Error::<T, I>::ChangeAlreadyScheduled
+            );
+            let now = frame_system::Pallet::<T>::block_number();
+            ensure!(at > now, Error::<T, I>::ChangeNotInFuture);
+            ensure!(
+                at <= now.saturating_add(T::MaxChangeDelay::get()),
+                Error::<T, I>::ChangeTooFarAhead
+            );
+
+            let call = <T as frame_system::Config>::RuntimeCall::from(
+                Call::<T, I>::apply_username_change {},
+            );
+            let call = Bounded::Inline(
+                call.encode()
+                    .try_into()
+                    .expect("apply_username_change is only a few bytes; qed"),
+            );
+            T::Scheduler::schedule_named(
+                Self::change_task(&who),
+                DispatchTime::At(at),
+                None,
+                HARD_DEADLINE,
+                frame_system::RawOrigin::Signed(who.clone()).into(),
+                call,
+            )?;
+
+            PendingChanges::<T, I>::insert(&who, (&username, at));
+            ReservedUsernames::<T, I>::insert(&username, &who);
+            Self::deposit_event(Event::UsernameChangeScheduled { who, username, at });
+
+            Ok(())
+        }
+
+        /// Cancel the scheduled username change of the signing account and release its username.
+        #[pallet::call_index(10)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(3, 4))]
+        pub fn cancel_username_change(origin: OriginFor<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            if !Self::cancel_change(&who) {
+                return Err(Error::<T, I>::NoPendingChange.into());
+            }
+
+            Ok(())
+        }
+
+        /// Apply the scheduled username change of the signing account.
+        ///
+        /// Dispatched by the scheduler at the block of the change. The account can apply it itself
+        /// afterwards, when the scheduled dispatch failed.
+        #[pallet::call_index(11)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(5, 6))]
+        pub fn apply_username_change(origin: OriginFor<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            let (username, at) =
+                PendingChanges::<T, I>::take(&who).ok_or(Error::<T, I>::NoPendingChange)?;
+            ensure!(
+                at <= frame_system::Pallet::<T>::block_number(),
+                Error::<T, I>::ChangeNotDue
+            );
+            ReservedUsernames::<T, I>::remove(&username);
+
+            let username = Self::validate_username(&who, username.into_inner())?;
+            Self::do_set_username(&who, username);
+
+            Ok(())
+        }
+    }
+
+    #[cfg(any(feature = "try-runtime", test))]
+    impl<T: Config<I>, I: 'static> Pallet<T, I> {
+        /// Check the storage invariants of the pallet:
+        ///
+        /// - `Usernames` and `UsernameOwners` are exact inverses of each other,
+        /// - `UsernameCount` is the number of accounts with a username,
+        /// - every listed username has an owner,
+        /// - `PendingChanges` and `ReservedUsernames` are exact inverses of each other.
+        pub fn do_try_state() -> Result<(), sp_runtime::TryRuntimeError> {
+            let mut count = 0u32;
+            for (who, username) in Usernames::<T, I>::iter() {
+                ensure!(
+                    UsernameOwners::<T, I>::get(&username).as_ref() == Some(&who),
+                    "Username is not indexed to its owner"
+                );
+                count += 1;
+            }
+            for (username, owner) in UsernameOwners::<T, I>::iter() {
+                ensure!(
+                    Usernames::<T, I>::get(&owner).as_ref() == Some(&username),
+                    "Indexed owner does not hold the username"
+                );
+            }
+            ensure!(
+                UsernameCount::<T, I>::get() == count,
+                "UsernameCount does not match the number of usernames"
+            );
+            for username in Listings::<T, I>::iter_keys() {
+                ensure!(
+                    UsernameOwners::<T, I>::contains_key(&username),
+                    "Listed username has no owner"
+                );
+            }
+            for (who, (username, _)) in PendingChanges::<T, I>::iter() {
+                ensure!(
+                    ReservedUsernames::<T, I>::get(&username).as_ref() == Some(&who),
+                    "Scheduled username is not reserved"
+                );
+            }
+            for (username, who) in ReservedUsernames::<T, I>::iter() {
+                ensure!(
+                    PendingChanges::<T, I>::get(&who).map(|(pending, _)| pending) == Some(username),
+                    "Reserved username is not scheduled"
+                );
+            }
+            Ok(())
+        }
+    }
 
-            // Validate username
-            ensure!(!username.is_empty(), Error::<T>::UsernameEmpty);
+    impl<T: Config<I>, I: 'static> Pallet<T, I> {
+        /// Check that `username` can be registered by `who`.
+        pub fn validate_username(
+            who: &T::AccountId,
+            username: Vec<u8>,
+        ) -> Result<BoundedVec<u8, T::MaxUsernameLength>, Error<T, I>> {
+            ensure!(!username.is_empty(), Error::<T, I>::UsernameEmpty);
             ensure!(
                 username.len() <= T::MaxUsernameLength::get() as usize,
-                Error::<T>::UsernameTooLong
+                Error::<T, I>::UsernameTooLong
             );
 
             let bounded_username: BoundedVec<_, _> = username
                 .try_into()
-                .map_err(|_| Error::<T>::UsernameTooLong)?;
+                .map_err(|_| Error::<T, I>::UsernameTooLong)?;
+
+            ensure!(
+                T::UsernameRules::is_allowed(&bounded_username),
+                Error::<T, I>::UsernameNotAllowed
+            );
+            ensure!(
+                !BlockedUsernames::<T, I>::contains_key(&bounded_username),
+                Error::<T, I>::UsernameBlocked
+            );
+
+            // Usernames are unique across accounts
+            if let Some(owner) = UsernameOwners::<T, I>::get(&bounded_username) {
+                ensure!(&owner == who, Error::<T, I>::UsernameTaken);
+            }
+            if let Some(holder) = ReservedUsernames::<T, I>::get(&bounded_username) {
+                ensure!(&holder == who, Error::<T, I>::UsernameReserved);
+            }
+
+            Ok(bounded_username)
+        }
+
+        /// Whether `set_username(username)` signed by `who` is a sponsored first registration.
+        ///
+        /// That is the case when sponsoring is enabled, `who` has never had a username nor a
+        /// sponsored registration, and `username` is valid. The per-block budget is enforced
+        /// separately by [`CheckSponsoredRegistration`].
+        pub fn is_sponsorable(who: &T::AccountId, username: &[u8]) -> bool {
+            T::MaxSponsoredPerBlock::get() > 0
+                && !Usernames::<T, I>::contains_key(who)
+                && !SponsoredAccounts::<T, I>::get(who)
+                && Self::validate_username(who, username.to_vec()).is_ok()
+        }
+
+        /// Number of sponsored registrations already included in the current block.
+        pub fn sponsored_in_block() -> u32 {
+            let (number, count) = SponsoredInBlock::<T, I>::get();
+            if number == frame_system::Pallet::<T>::block_number() {
+                count
+            } else {
+                0
+            }
+        }
+
+        /// The username change scheduled by `who`, with the block it takes effect at.
+        pub fn pending_change(who: &T::AccountId) -> Option<(Vec<u8>, BlockNumberFor<T>)> {
+            PendingChanges::<T, I>::get(who).map(|(username, at)| (username.into_inner(), at))
+        }
+
+        /// All scheduled username changes, with their account and block.
+        pub fn pending_changes() -> Vec<(T::AccountId, Vec<u8>, BlockNumberFor<T>)> {
+            PendingChanges::<T, I>::iter()
+                .map(|(who, (username, at))| (who, username.into_inner(), at))
+                .collect()
+        }
+
+        /// Cancel the scheduled username change of `who` and release its username, returning
+        /// whether there was one.
+        fn cancel_change(who: &T::AccountId) -> bool {
+            let Some((username, _)) = PendingChanges::<T, I>::take(who) else {
+                return false;
+            };
+            ReservedUsernames::<T, I>::remove(&username);
+            // The task is gone when its dispatch failed, the change is cancelled all the same
+            let _ = T::Scheduler::cancel_named(Self::change_task(who));
+
+            Self::deposit_event(Event::UsernameChangeCancelled {
+                who: who.clone(),
+                username,
+            });
+            true
+        }
+
+        /// Name of the scheduler task applying the username change of `who`, unique per instance.
+        fn change_task(who: &T::AccountId) -> TaskName {
+            (b"username/change", Self::name(), who).using_encoded(sp_io::hashing::blake2_256)
+        }
+
+        /// Set the already validated `username` as the username of `who`.
+        fn do_set_username(who: &T::AccountId, username: BoundedVec<u8, T::MaxUsernameLength>) {
+            let old = Usernames::<T, I>::get(who);
 
-            // Check if username already exists
-            let exists = Usernames::<T>::contains_key(&who);
+            if let Some(old) = &old {
+                UsernameOwners::<T, I>::remove(old);
+                if old != &username {
+                    Listings::<T, I>::remove(old);
+                }
+            }
+            Usernames::<T, I>::insert(who, username.clone());
+            UsernameOwners::<T, I>::insert(&username, who);
+            if old.is_none() {
+                UsernameCount::<T, I>::mutate(|count| *count = count.saturating_add(1));
+            }
 
-            // Update storage
-            Usernames::<T>::insert(&who, bounded_username.clone());
+            T::OnUsernameChanged::on_username_changed(
+                who,
+                old.as_ref().map(|v| &v[..]),
+                Some(&username[..]),
+            );
 
-            // Emit appropriate event
-            if exists {
+            if old.is_some() {
                 Self::deposit_event(Event::UsernameUpdated {
-                    who,
-                    username: bounded_username,
+                    who: who.clone(),
+                    username,
                 });
             } else {
                 Self::deposit_event(Event::UsernameSet {
-                    who,
-                    username: bounded_username,
+                    who: who.clone(),
+                    username,
                 });
             }
+        }
+
+        /// Remove the username of `who` along with its listing, and return it.
+        fn remove_username(
+            who: &T::AccountId,
+        ) -> Result<BoundedVec<u8, T::MaxUsernameLength>, Error<T, I>> {
+            let old = Usernames::<T, I>::take(who).ok_or(Error::<T, I>::NoUsername)?;
+            UsernameOwners::<T, I>::remove(&old);
+            Listings::<T, I>::remove(&old);
+            UsernameCount::<T, I>::mutate(|count| *count = count.saturating_sub(1));
+
+            T::OnUsernameChanged::on_username_changed(who, Some(&old[..]), None);
+            Ok(old)
+        }
+
+        /// Move the username of `from` to `to`, which must not have one.
+        pub fn do_transfer(from: &T::AccountId, to: &T::AccountId) -> DispatchResult {
+            ensure!(
+                !Usernames::<T, I>::contains_key(to),
+                Error::<T, I>::DestinationHasUsername
+            );
+            let username = Usernames::<T, I>::take(from).ok_or(Error::<T, I>::NoUsername)?;
+
+            Usernames::<T, I>::insert(to, username.clone());
+            UsernameOwners::<T, I>::insert(&username, to);
+            Listings::<T, I>::remove(&username);
+
+            T::OnUsernameChanged::on_username_changed(from, Some(&username[..]), None);
+            T::OnUsernameChanged::on_username_changed(to, None, Some(&username[..]));
+            Self::deposit_event(Event::UsernameTransferred {
+                from: from.clone(),
+                to: to.clone(),
+                username,
+            });
 
             Ok(())
         }
+
+        /// Up to `limit` accounts with their usernames, in storage order, starting after
+        /// `start_after` when it is given.
+        pub fn usernames_page(
+            start_after: Option<T::AccountId>,
+            limit: u32,
+        ) -> Vec<(T::AccountId, Vec<u8>)> {
+            let iter = match start_after {
+                Some(who) => Usernames::<T, I>::iter_from(Usernames::<T, I>::hashed_key_for(who)),
+                None => Usernames::<T, I>::iter(),
+            };
+            iter.take(limit as usize)
+                .map(|(who, username)| (who, username.into_inner()))
+                .collect()
+        }
+
+        /// Number of accounts with a username.
+        pub fn username_count() -> u32 {
+            UsernameCount::<T, I>::get()
+        }
+
+        /// All usernames listed for sale, with their owner and asking price.
+        pub fn listings() -> Vec<(Vec<u8>, T::AccountId, BalanceOf<T, I>)> {
+            Listings::<T, I>::iter()
+                .filter_map(|(username, price)| {
+                    let owner = UsernameOwners::<T, I>::get(&username)?;
+                    Some((username.into_inner(), owner, price))
+                })
+                .collect()
+        }
+
+        /// The owner and asking price of `username`, if it is listed for sale.
+        pub fn listing(username: &[u8]) -> Option<(T::AccountId, BalanceOf<T, I>)> {
+            let username: BoundedVec<u8, T::MaxUsernameLength> =
+                username.to_vec().try_into().ok()?;
+            let price = Listings::<T, I>::get(&username)?;
+            Some((UsernameOwners::<T, I>::get(&username)?, price))
+        }
+
+        fn note_sponsored(who: &T::AccountId) {
+            SponsoredAccounts::<T, I>::insert(who, true);
+            SponsoredInBlock::<T, I>::put((
+                frame_system::Pallet::<T>::block_number(),
+                Self::sponsored_in_block().saturating_add(1),
+            ));
+            Self::deposit_event(Event::RegistrationSponsored { who: who.clone() });
+        }
+    }
+
+    impl<T: Config<I>, I: 'static> UsernameProvider<T::AccountId> for Pallet<T, I> {
+        fn username_of(who: &T::AccountId) -> Option<Vec<u8>> {
+            Usernames::<T, I>::get(who).map(|v| v.into_inner())
+        }
+
+        fn account_of(username: &[u8]) -> Option<T::AccountId> {
+            let username: BoundedVec<u8, T::MaxUsernameLength> =
+                username.to_vec().try_into().ok()?;
+            UsernameOwners::<T, I>::get(username)
+        }
     }
 
     decl_runtime_apis! {
+        #[api_version(7)]
         pub trait UsernameStorageApi<AccountId: codec::Codec> {
             fn get_username(account_id: AccountId) -> Option<Vec<u8>>;
             fn set_username(account_id: AccountId, username: Vec<u8>) -> Result<(), sp_runtime::DispatchError>;
+            /// The usernames of `account_ids`, in the same order.
+            #[api_version(2)]
+            fn get_usernames(account_ids: Vec<AccountId>) -> Vec<Option<Vec<u8>>>;
+            /// The account owning `username`, if any.
+            #[api_version(3)]
+            fn get_account(username: Vec<u8>) -> Option<AccountId>;
+            /// Up to `limit` accounts with their usernames, starting after `start_after`.
+            #[api_version(3)]
+            fn usernames_page(start_after: Option<AccountId>, limit: u32) -> Vec<(AccountId, Vec<u8>)>;
+            /// Number of accounts with a username.
+            #[api_version(4)]
+            fn username_count() -> u32;
+            /// The username change scheduled by `account_id`, with the block it takes effect at.
+            #[api_version(5)]
+            fn pending_username_change(account_id: AccountId) -> Option<(Vec<u8>, u32)>;
+            /// All scheduled username changes, with their account and block.
+            #[api_version(5)]
+            fn pending_username_changes() -> Vec<(AccountId, Vec<u8>, u32)>;
+            /// Names of the username registries of the runtime, one per instance of the pallet.
+            /// The methods without a `registry` serve the first one.
+            #[api_version(6)]
+            fn registries() -> Vec<Vec<u8>>;
+            /// The usernames of `account_ids` in `registry`, in the same order, `None` when the
+            /// runtime has no such registry.
+            #[api_version(6)]
+            fn registry_usernames(registry: Vec<u8>, account_ids: Vec<AccountId>) -> Option<Vec<Option<Vec<u8>>>>;
+            /// The account owning `username` in `registry`, `None` when the runtime has no such
+            /// registry.
+            #[api_version(6)]
+            fn registry_account(registry: Vec<u8>, username: Vec<u8>) -> Option<Option<AccountId>>;
+            /// Dry-run `set_username` in `registry`, `None` when the runtime has no such registry.
+            #[api_version(6)]
+            fn registry_set_username(registry: Vec<u8>, account_id: AccountId, username: Vec<u8>) -> Option<Result<(), sp_runtime::DispatchError>>;
+            /// Name of the pallet instance holding `registry`, which prefixes its storage keys.
+            /// `None` when the runtime has no such registry.
+            #[api_version(7)]
+            fn registry_pallet(registry: Vec<u8>) -> Option<Vec<u8>>;
+            /// The username change scheduled by `account_id` in `registry`, `None` when the
+            /// runtime has no such registry.
+            #[api_version(7)]
+            fn registry_pending_username_change(registry: Vec<u8>, account_id: AccountId) -> Option<Option<(Vec<u8>, u32)>>;
+            /// All scheduled username changes of `registry`, `None` when the runtime has no such
+            /// registry.
+            #[api_version(7)]
+            fn registry_pending_username_changes(registry: Vec<u8>) -> Option<Vec<(AccountId, Vec<u8>, u32)>>;
+            /// `usernames_page` of `registry`, `None` when the runtime has no such registry.
+            #[api_version(7)]
+            fn registry_usernames_page(registry: Vec<u8>, start_after: Option<AccountId>, limit: u32) -> Option<Vec<(AccountId, Vec<u8>)>>;
+            /// `username_count` of `registry`, `None` when the runtime has no such registry.
+            #[api_version(7)]
+            fn registry_username_count(registry: Vec<u8>) -> Option<u32>;
+        }
+
+        /// Usernames for sale. The methods without a `registry` serve the default registry.
+        #[api_version(2)]
+        pub trait UsernameMarketApi<AccountId: codec::Codec, Balance: codec::Codec> {
+            /// All usernames listed for sale, with their owner and asking price.
+            fn listings() -> Vec<(Vec<u8>, AccountId, Balance)>;
+            /// The owner and asking price of `username`, if it is listed for sale.
+            fn listing(username: Vec<u8>) -> Option<(AccountId, Balance)>;
+            /// All usernames listed for sale in `registry`, `None` when the runtime has no such
+            /// registry.
+            #[api_version(2)]
+            fn registry_listings(registry: Vec<u8>) -> Option<Vec<(Vec<u8>, AccountId, Balance)>>;
+            /// The owner and asking price of `username` in `registry`, `None` when the runtime
+            /// has no such registry.
+            #[api_version(2)]
+            fn registry_listing(registry: Vec<u8>, username: Vec<u8>) -> Option<Option<(AccountId, Balance)>>;
         }
     }
 }
@@ -123,7 +943,7 @@ mod benchmarking {
     use frame_benchmarking::v2::*;
     use frame_system::RawOrigin;
 
-    #[benchmarks]
+    #[instance_benchmarks]
     mod set_username {
         use super::*;
 
@@ -141,14 +961,26 @@ mod benchmarking {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use codec::Encode;
     use frame_support::{
-        assert_noop, assert_ok, parameter_types,
-        traits::{ConstU32, Everything},
+        assert_noop, assert_ok, derive_impl,
+        dispatch::GetDispatchInfo,
+        instances::Instance1,
+        parameter_types,
+        traits::{
+            tokens::nonfungibles_v2::{Inspect, Transfer},
+            ConstU32, ConstU64, EqualPrivilegeOnly, Everything, Hooks,
+        },
+        weights::Weight,
+        BoundedVec,
     };
     use sp_core::H256;
     use sp_runtime::{
-        traits::{BlakeTwo256, IdentityLookup},
-        BuildStorage,
+        traits::{BlakeTwo256, DispatchTransaction, IdentityLookup, StaticLookup},
+        transaction_validity::{
+            InvalidTransaction, TransactionSource, TransactionValidityError, ValidTransaction,
+        },
+        BuildStorage, MultiAddress,
     };
     use sp_std::convert::TryInto;
 
@@ -175,6 +1007,15 @@ mod tests {
 
         #[runtime::pallet_index(1)]
         pub type UsernameStorage = crate::pallet;
+
+        #[runtime::pallet_index(2)]
+        pub type Balances = pallet_balances;
+
+        #[runtime::pallet_index(3)]
+        pub type Scheduler = pallet_scheduler;
+
+        #[runtime::pallet_index(4)]
+        pub type BotUsernames = crate::pallet<Instance1>;
     }
 
     parameter_types! {
@@ -200,7 +1041,7 @@ mod tests {
         type DbWeight = ();
         type Version = ();
         type PalletInfo = PalletInfo;
-        type AccountData = ();
+        type AccountData = pallet_balances::AccountData<u64>;
         type OnNewAccount = ();
         type OnKilledAccount = ();
         type SystemWeightInfo = ();
@@ -216,21 +1057,156 @@ mod tests {
         type PostTransactions = ();
     }
 
-    impl Config for Test {
+    #[derive_impl(pallet_balances::config_preludes::TestDefaultConfig)]
+    impl pallet_balances::Config for Test {
+        type AccountStore = System;
+    }
+
+    parameter_types! {
+        pub const MaximumSchedulerWeight: Weight = Weight::MAX;
+    }
+
+    impl pallet_scheduler::Config for Test {
         type RuntimeEvent = RuntimeEvent;
-        type MaxUsernameLength = MaxUsernameLength;
+        type RuntimeOrigin = RuntimeOrigin;
+        type PalletsOrigin = OriginCaller;
+        type RuntimeCall = RuntimeCall;
+        type MaximumWeight = MaximumSchedulerWeight;
+        type ScheduleOrigin = frame_system::EnsureRoot<u64>;
+        type MaxScheduledPerBlock = ConstU32<10>;
+        type WeightInfo = ();
+        type OriginPrivilegeCmp = EqualPrivilegeOnly;
+        type Preimages = ();
+        type BlockNumberProvider = System;
     }
 
-    fn new_test_ext() -> sp_io::TestExternalities {
-        let t = frame_system::GenesisConfig::<Test>::default()
-            .build_storage()
-            .unwrap();
-        let mut ext = sp_io::TestExternalities::new(t);
-        ext.execute_with(|| System::set_block_number(1));
-        ext
+    parameter_types! {
+        pub static Changes: Vec<(u64, Option<Vec<u8>>, Option<Vec<u8>>)> = Vec::new();
     }
 
-    #[test]
+    pub struct RecordChanges;
+    impl OnUsernameChanged<u64> for RecordChanges {
+        fn on_username_changed(who: &u64, old: Option<&[u8]>, new: Option<&[u8]>) {
+            Changes::mutate(|c| c.push((*who, old.map(|v| v.to_vec()), new.map(|v| v.to_vec()))));
+        }
+    }
+
+    impl Config for Test {
+        type RuntimeEvent = RuntimeEvent;
+        type MaxUsernameLength = MaxUsernameLength;
+        type UsernameRules = ();
+        type OnUsernameChanged = RecordChanges;
+        type MaxSponsoredPerBlock = ConstU32<2>;
+        type Currency = Balances;
+        type AdminOrigin = frame_system::EnsureRoot<u64>;
+        type PalletsOrigin = OriginCaller;
+        type Scheduler = Scheduler;
+        type MaxChangeDelay = ConstU64<100>;
+    }
+
+    /// Bot usernames end with `bot`.
+    pub struct BotRules;
+    impl UsernameRules for BotRules {
+        fn is_allowed(username: &[u8]) -> bool {
+            username.ends_with(b"bot")
+        }
+    }
+
+    impl Config<Instance1> for Test {
+        type RuntimeEvent = RuntimeEvent;
+        type MaxUsernameLength = ConstU32<8>;
+        type UsernameRules = BotRules;
+        type OnUsernameChanged = ();
+        type MaxSponsoredPerBlock = ConstU32<0>;
+        type Currency = Balances;
+        type AdminOrigin = frame_system::EnsureRoot<u64>;
+        type PalletsOrigin = OriginCaller;
+        type Scheduler = Scheduler;
+        type MaxChangeDelay = ConstU64<100>;
+    }
+
+    fn new_test_ext() -> sp_io::TestExternalities {
+        let mut t = frame_system::GenesisConfig::<Test>::default()
+            .build_storage()
+            .unwrap();
+        pallet_balances::GenesisConfig::<Test> {
+            balances: vec![(1, 100), (2, 100), (3, 100)],
+            ..Default::default()
+        }
+        .assimilate_storage(&mut t)
+        .unwrap();
+        let mut ext = sp_io::TestExternalities::new(t);
+        ext.execute_with(|| System::set_block_number(1));
+        ext
+    }
+
+    #[test]
+    fn test_genesis_usernames() {
+        let mut t = frame_system::GenesisConfig::<Test>::default()
+            .build_storage()
+            .unwrap();
+        GenesisConfig::<Test> {
+            usernames: vec![(1, b"alice".to_vec()), (2, b"bob".to_vec())],
+            ..Default::default()
+        }
+        .assimilate_storage(&mut t)
+        .unwrap();
+
+        sp_io::TestExternalities::new(t).execute_with(|| {
+            assert_eq!(
+                Usernames::<Test>::get(1),
+                Some(b"alice".to_vec().try_into().unwrap())
+            );
+            let bob: BoundedVec<u8, MaxUsernameLength> = b"bob".to_vec().try_into().unwrap();
+            assert_eq!(UsernameOwners::<Test>::get(bob), Some(2));
+            assert_eq!(UsernameCount::<Test>::get(), 2);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Genesis usernames are valid and unique")]
+    fn test_genesis_duplicate_username() {
+        let _ = GenesisConfig::<Test> {
+            usernames: vec![(1, b"alice".to_vec()), (2, b"alice".to_vec())],
+            ..Default::default()
+        }
+        .build_storage();
+    }
+
+    #[test]
+    fn test_try_state() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(2),
+                b"bob".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::transfer_username(
+                RuntimeOrigin::signed(1),
+                3
+            ));
+            assert_ok!(Pallet::<Test>::list_username(RuntimeOrigin::signed(3), 10));
+            assert_ok!(Pallet::<Test>::do_try_state());
+
+            // A username missing from the reverse index
+            Usernames::<Test>::insert(
+                1,
+                frame_support::BoundedVec::truncate_from(b"carol".to_vec()),
+            );
+            assert!(Pallet::<Test>::do_try_state().is_err());
+            Usernames::<Test>::remove(1);
+            assert_ok!(Pallet::<Test>::do_try_state());
+
+            // A stale counter
+            UsernameCount::<Test>::put(5);
+            assert!(Pallet::<Test>::do_try_state().is_err());
+        });
+    }
+
+    #[test]
     fn test_set_username() {
         new_test_ext().execute_with(|| {
             let username = b"test_username".to_vec();
@@ -265,4 +1241,824 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn test_clear_username() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"test_username".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::clear_username(RuntimeOrigin::signed(1)));
+            assert_eq!(Usernames::<Test>::get(1), None);
+            assert_noop!(
+                Pallet::<Test>::clear_username(RuntimeOrigin::signed(1)),
+                Error::<Test>::NoUsername
+            );
+        });
+    }
+
+    #[test]
+    fn test_force_clear_username() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"test_username".to_vec()
+            ));
+            assert_noop!(
+                Pallet::<Test>::force_clear_username(RuntimeOrigin::signed(2), 1),
+                sp_runtime::DispatchError::BadOrigin
+            );
+            assert_ok!(Pallet::<Test>::force_clear_username(
+                RuntimeOrigin::root(),
+                1
+            ));
+            System::assert_last_event(
+                Event::<Test>::UsernameForceCleared {
+                    who: 1,
+                    username: b"test_username".to_vec().try_into().unwrap(),
+                }
+                .into(),
+            );
+            assert_eq!(Usernames::<Test>::get(1), None);
+            assert_eq!(UsernameCount::<Test>::get(), 0);
+            assert_noop!(
+                Pallet::<Test>::force_clear_username(RuntimeOrigin::root(), 1),
+                Error::<Test>::NoUsername
+            );
+        });
+    }
+
+    #[test]
+    fn test_block_username() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                Pallet::<Test>::block_username(RuntimeOrigin::signed(1), b"admin".to_vec()),
+                sp_runtime::DispatchError::BadOrigin
+            );
+            assert_ok!(Pallet::<Test>::block_username(
+                RuntimeOrigin::root(),
+                b"admin".to_vec()
+            ));
+            assert_noop!(
+                Pallet::<Test>::block_username(RuntimeOrigin::root(), b"admin".to_vec()),
+                Error::<Test>::AlreadyBlocked
+            );
+            assert_noop!(
+                Pallet::<Test>::set_username(RuntimeOrigin::signed(1), b"admin".to_vec()),
+                Error::<Test>::UsernameBlocked
+            );
+            assert!(!Pallet::<Test>::is_sponsorable(&1, b"admin"));
+
+            assert_ok!(Pallet::<Test>::unblock_username(
+                RuntimeOrigin::root(),
+                b"admin".to_vec()
+            ));
+            assert_noop!(
+                Pallet::<Test>::unblock_username(RuntimeOrigin::root(), b"admin".to_vec()),
+                Error::<Test>::NotBlocked
+            );
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"admin".to_vec()
+            ));
+        });
+    }
+
+    #[test]
+    fn test_on_username_changed_hook() {
+        new_test_ext().execute_with(|| {
+            Changes::take();
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice2".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::clear_username(RuntimeOrigin::signed(1)));
+            assert_eq!(
+                Changes::get(),
+                vec![
+                    (1, None, Some(b"alice".to_vec())),
+                    (1, Some(b"alice".to_vec()), Some(b"alice2".to_vec())),
+                    (1, Some(b"alice2".to_vec()), None),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_set_username_taken() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_noop!(
+                Pallet::<Test>::set_username(RuntimeOrigin::signed(2), b"alice".to_vec()),
+                Error::<Test>::UsernameTaken
+            );
+
+            // The name is released once its owner moves to another one
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice2".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(2),
+                b"alice".to_vec()
+            ));
+            assert_eq!(Pallet::<Test>::account_of(b"alice"), Some(2));
+            assert_eq!(Pallet::<Test>::account_of(b"alice2"), Some(1));
+        });
+    }
+
+    #[test]
+    fn test_username_lookup() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_eq!(
+                UsernameLookup::<Test>::lookup(MultiAddress::Raw(b"alice".to_vec())),
+                Ok(1)
+            );
+            assert_eq!(UsernameLookup::<Test>::lookup(MultiAddress::Id(2)), Ok(2));
+            assert!(UsernameLookup::<Test>::lookup(MultiAddress::Raw(b"bob".to_vec())).is_err());
+
+            assert_ok!(Pallet::<Test>::clear_username(RuntimeOrigin::signed(1)));
+            assert!(UsernameLookup::<Test>::lookup(MultiAddress::Raw(b"alice".to_vec())).is_err());
+        });
+    }
+
+    #[test]
+    fn test_sponsored_first_registration() {
+        new_test_ext().execute_with(|| {
+            assert!(Pallet::<Test>::is_sponsorable(&1, b"alice"));
+            assert!(!Pallet::<Test>::is_sponsorable(&1, b""));
+
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert!(SponsoredAccounts::<Test>::get(1));
+            assert_eq!(Pallet::<Test>::sponsored_in_block(), 1);
+            System::assert_has_event(Event::<Test>::RegistrationSponsored { who: 1 }.into());
+
+            // Only the first registration of an account is sponsored
+            assert!(!Pallet::<Test>::is_sponsorable(&1, b"alice2"));
+            assert_ok!(Pallet::<Test>::clear_username(RuntimeOrigin::signed(1)));
+            assert!(!Pallet::<Test>::is_sponsorable(&1, b"alice2"));
+
+            // Taken names are not sponsored
+            assert!(!Pallet::<Test>::is_sponsorable(&2, b"alice"));
+            assert!(Pallet::<Test>::is_sponsorable(&2, b"bob"));
+        });
+    }
+
+    /// Validate `set_username` of `who` at `nonce` with `CheckUsername` and `CheckNonce`, in the
+    /// order of the runtime.
+    fn validate_set_username(
+        who: u64,
+        username: &[u8],
+        nonce: u64,
+    ) -> Result<ValidTransaction, TransactionValidityError> {
+        let call = RuntimeCall::UsernameStorage(Call::set_username {
+            username: username.to_vec(),
+        });
+        (
+            CheckUsername::<Test>::new(),
+            frame_system::CheckNonce::<Test>::from(nonce),
+        )
+            .validate_only(
+                RuntimeOrigin::signed(who),
+                &call,
+                &call.get_dispatch_info(),
+                0,
+                TransactionSource::External,
+                0,
+            )
+            .map(|(validity, _, _)| validity)
+    }
+
+    #[test]
+    fn test_check_username_extension() {
+        new_test_ext().execute_with(|| {
+            let validate = |who: u64, username: &[u8]| validate_set_username(who, username, 0);
+            let rejected =
+                |error: Error<Test>| -> Result<ValidTransaction, TransactionValidityError> {
+                    Err(InvalidTransaction::Custom(error.encode()[0]).into())
+                };
+
+            assert_eq!(validate(1, b""), rejected(Error::<Test>::UsernameEmpty));
+            assert_eq!(
+                validate(1, &[b'a'; 33]),
+                rejected(Error::<Test>::UsernameTooLong)
+            );
+
+            let validity = validate(1, b"alice").unwrap();
+            let tag = ("UsernameStorage", (1u64, b"username", 0u64)).encode();
+            assert!(validity.provides.contains(&tag));
+
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_eq!(
+                validate(2, b"alice"),
+                rejected(Error::<Test>::UsernameTaken)
+            );
+
+            // The nonce is read from `CheckNonce`, which must come next
+            let call = RuntimeCall::UsernameStorage(Call::set_username {
+                username: b"bob".to_vec(),
+            });
+            assert_eq!(
+                CheckUsername::<Test>::new()
+                    .validate_only(
+                        RuntimeOrigin::signed(2),
+                        &call,
+                        &call.get_dispatch_info(),
+                        0,
+                        TransactionSource::External,
+                        0,
+                    )
+                    .map(|(validity, _, _)| validity),
+                Err(InvalidTransaction::Call.into())
+            );
+
+            // Other calls are left alone
+            let call = RuntimeCall::UsernameStorage(Call::clear_username {});
+            assert_ok!(CheckUsername::<Test>::new().validate_only(
+                RuntimeOrigin::signed(2),
+                &call,
+                &call.get_dispatch_info(),
+                0,
+                TransactionSource::External,
+                0,
+            ));
+        });
+    }
+
+    #[test]
+    fn test_check_username_replacement() {
+        new_test_ext().execute_with(|| {
+            // The pool replaces the pending transactions that provide a tag of a new transaction
+            // when the new one has a higher priority, and rejects the new one otherwise.
+            let replaces = |new: &ValidTransaction, pending: &ValidTransaction| {
+                new.provides
+                    .iter()
+                    .any(|tag| pending.provides.contains(tag))
+            };
+
+            let pending = validate_set_username(1, b"alice", 0).unwrap();
+
+            // A change signed again at the same nonce replaces the pending one
+            let newer = validate_set_username(1, b"alice2", 0).unwrap();
+            assert!(replaces(&newer, &pending));
+
+            // A change at the next nonce is applied after the pending one
+            let next = validate_set_username(1, b"alice2", 1).unwrap();
+            assert!(!replaces(&next, &pending));
+            assert!(next
+                .requires
+                .iter()
+                .all(|tag| pending.provides.contains(tag)));
+            assert!(!next.requires.is_empty());
+
+            // Other accounts and registries are not affected
+            let other = validate_set_username(2, b"bob", 0).unwrap();
+            assert!(!replaces(&other, &pending));
+            let call = RuntimeCall::BotUsernames(Call::set_username {
+                username: b"alicebot".to_vec(),
+            });
+            let bot = (
+                CheckUsername::<Test, Instance1>::new(),
+                frame_system::CheckNonce::<Test>::from(0),
+            )
+                .validate_only(
+                    RuntimeOrigin::signed(1),
+                    &call,
+                    &call.get_dispatch_info(),
+                    0,
+                    TransactionSource::External,
+                    0,
+                )
+                .map(|(validity, _, _)| validity)
+                .unwrap();
+            let tag = ("UsernameStorage", (1u64, b"username", 0u64)).encode();
+            assert!(pending.provides.contains(&tag));
+            assert!(!bot.provides.contains(&tag));
+        });
+    }
+
+    #[test]
+    fn test_sponsored_in_block_resets() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(2),
+                b"bob".to_vec()
+            ));
+            assert_eq!(Pallet::<Test>::sponsored_in_block(), 2);
+
+            System::set_block_number(2);
+            assert_eq!(Pallet::<Test>::sponsored_in_block(), 0);
+        });
+    }
+
+    #[test]
+    fn test_transfer_username() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(3),
+                b"carol".to_vec()
+            ));
+            assert_noop!(
+                Pallet::<Test>::transfer_username(RuntimeOrigin::signed(1), 3),
+                Error::<Test>::DestinationHasUsername
+            );
+
+            assert_ok!(Pallet::<Test>::transfer_username(
+                RuntimeOrigin::signed(1),
+                2
+            ));
+            assert_eq!(Usernames::<Test>::get(1), None);
+            assert_eq!(Pallet::<Test>::username_of(&2), Some(b"alice".to_vec()));
+            assert_eq!(Pallet::<Test>::account_of(b"alice"), Some(2));
+            assert_noop!(
+                Pallet::<Test>::transfer_username(RuntimeOrigin::signed(1), 4),
+                Error::<Test>::NoUsername
+            );
+        });
+    }
+
+    #[test]
+    fn test_username_nonfungibles() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            let item: BoundedVec<u8, MaxUsernameLength> = b"alice".to_vec().try_into().unwrap();
+            assert_eq!(<Pallet<Test> as Inspect<u64>>::owner(&(), &item), Some(1));
+
+            assert_ok!(<Pallet<Test> as Transfer<u64>>::transfer(&(), &item, &2));
+            assert_eq!(<Pallet<Test> as Inspect<u64>>::owner(&(), &item), Some(2));
+            assert_eq!(Usernames::<Test>::get(2), Some(item));
+
+            let unknown: BoundedVec<u8, MaxUsernameLength> = b"bob".to_vec().try_into().unwrap();
+            assert!(<Pallet<Test> as Transfer<u64>>::transfer(&(), &unknown, &3).is_err());
+        });
+    }
+
+    #[test]
+    fn test_buy_listed_username() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_noop!(
+                Pallet::<Test>::buy_username(RuntimeOrigin::signed(2), b"alice".to_vec(), 30),
+                Error::<Test>::NotListed
+            );
+
+            assert_ok!(Pallet::<Test>::list_username(RuntimeOrigin::signed(1), 30));
+            assert_eq!(Pallet::<Test>::listing(b"alice"), Some((1, 30)));
+            assert_noop!(
+                Pallet::<Test>::buy_username(RuntimeOrigin::signed(1), b"alice".to_vec(), 30),
+                Error::<Test>::CannotBuyOwnUsername
+            );
+
+            // The owner raised the price before the purchase was included
+            assert_ok!(Pallet::<Test>::list_username(RuntimeOrigin::signed(1), 50));
+            assert_noop!(
+                Pallet::<Test>::buy_username(RuntimeOrigin::signed(2), b"alice".to_vec(), 30),
+                Error::<Test>::PriceTooHigh
+            );
+
+            assert_ok!(Pallet::<Test>::list_username(RuntimeOrigin::signed(1), 30));
+            assert_ok!(Pallet::<Test>::buy_username(
+                RuntimeOrigin::signed(2),
+                b"alice".to_vec(),
+                40
+            ));
+            assert_eq!(Pallet::<Test>::account_of(b"alice"), Some(2));
+            assert_eq!(Usernames::<Test>::get(1), None);
+            assert_eq!(Balances::free_balance(1), 130);
+            assert_eq!(Balances::free_balance(2), 70);
+            assert_eq!(Pallet::<Test>::listing(b"alice"), None);
+        });
+    }
+
+    #[test]
+    fn test_buy_username_requires_funds() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::list_username(
+                RuntimeOrigin::signed(1),
+                1_000
+            ));
+            assert!(Pallet::<Test>::buy_username(
+                RuntimeOrigin::signed(2),
+                b"alice".to_vec(),
+                1_000
+            )
+            .is_err());
+            assert_eq!(Pallet::<Test>::account_of(b"alice"), Some(1));
+        });
+    }
+
+    #[test]
+    fn test_listing_cleared_on_transfer() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::list_username(RuntimeOrigin::signed(1), 30));
+            assert_eq!(Pallet::<Test>::listings(), vec![(b"alice".to_vec(), 1, 30)]);
+
+            assert_ok!(Pallet::<Test>::transfer_username(
+                RuntimeOrigin::signed(1),
+                2
+            ));
+            assert!(Pallet::<Test>::listings().is_empty());
+            assert_noop!(
+                Pallet::<Test>::delist_username(RuntimeOrigin::signed(2)),
+                Error::<Test>::NotListed
+            );
+        });
+    }
+
+    #[test]
+    fn test_usernames_page() {
+        new_test_ext().execute_with(|| {
+            for (who, name) in [(1, b"alice"), (2, b"bobby"), (3, b"carol")] {
+                assert_ok!(Pallet::<Test>::set_username(
+                    RuntimeOrigin::signed(who),
+                    name.to_vec()
+                ));
+            }
+
+            let first = Pallet::<Test>::usernames_page(None, 2);
+            assert_eq!(first.len(), 2);
+            let rest = Pallet::<Test>::usernames_page(Some(first[1].0), 2);
+            assert_eq!(rest.len(), 1);
+
+            let mut all: Vec<_> = first.into_iter().chain(rest).collect();
+            all.sort();
+            assert_eq!(
+                all,
+                vec![
+                    (1, b"alice".to_vec()),
+                    (2, b"bobby".to_vec()),
+                    (3, b"carol".to_vec())
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_username_count() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice2".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(2),
+                b"bobby".to_vec()
+            ));
+            assert_eq!(UsernameCount::<Test>::get(), 2);
+
+            assert_ok!(Pallet::<Test>::transfer_username(
+                RuntimeOrigin::signed(2),
+                3
+            ));
+            assert_eq!(UsernameCount::<Test>::get(), 2);
+
+            assert_ok!(Pallet::<Test>::clear_username(RuntimeOrigin::signed(1)));
+            assert_eq!(UsernameCount::<Test>::get(), 1);
+        });
+    }
+
+    fn run_to_block(n: u64) {
+        while System::block_number() < n {
+            System::set_block_number(System::block_number() + 1);
+            Scheduler::on_initialize(System::block_number());
+        }
+    }
+
+    #[test]
+    fn test_schedule_username_change() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_noop!(
+                Pallet::<Test>::schedule_username_change(
+                    RuntimeOrigin::signed(1),
+                    b"acme".to_vec(),
+                    1
+                ),
+                Error::<Test>::ChangeNotInFuture
+            );
+            assert_noop!(
+                Pallet::<Test>::schedule_username_change(
+                    RuntimeOrigin::signed(1),
+                    b"acme".to_vec(),
+                    102
+                ),
+                Error::<Test>::ChangeTooFarAhead
+            );
+
+            assert_ok!(Pallet::<Test>::schedule_username_change(
+                RuntimeOrigin::signed(1),
+                b"acme".to_vec(),
+                5
+            ));
+            System::assert_last_event(
+                Event::<Test>::UsernameChangeScheduled {
+                    who: 1,
+                    username: BoundedVec::truncate_from(b"acme".to_vec()),
+                    at: 5,
+                }
+                .into(),
+            );
+            assert_eq!(
+                Pallet::<Test>::pending_changes(),
+                vec![(1, b"acme".to_vec(), 5)]
+            );
+            assert_noop!(
+                Pallet::<Test>::schedule_username_change(
+                    RuntimeOrigin::signed(1),
+                    b"acme2".to_vec(),
+                    6
+                ),
+                Error::<Test>::ChangeAlreadyScheduled
+            );
+            assert_ok!(Pallet::<Test>::do_try_state());
+
+            // The username is reserved for the account until the change
+            assert_noop!(
+                Pallet::<Test>::set_username(RuntimeOrigin::signed(2), b"acme".to_vec()),
+                Error::<Test>::UsernameReserved
+            );
+            assert_noop!(
+                Pallet::<Test>::apply_username_change(RuntimeOrigin::signed(1)),
+                Error::<Test>::ChangeNotDue
+            );
+
+            run_to_block(4);
+            assert_eq!(
+                Usernames::<Test>::get(1),
+                Some(BoundedVec::truncate_from(b"alice".to_vec()))
+            );
+            run_to_block(5);
+            assert_eq!(
+                Usernames::<Test>::get(1),
+                Some(BoundedVec::truncate_from(b"acme".to_vec()))
+            );
+            assert_eq!(
+                UsernameOwners::<Test>::get(BoundedVec::truncate_from(b"alice".to_vec())),
+                None
+            );
+            assert_eq!(Pallet::<Test>::pending_change(&1), None);
+            assert_eq!(ReservedUsernames::<Test>::iter().count(), 0);
+            assert_ok!(Pallet::<Test>::do_try_state());
+        });
+    }
+
+    #[test]
+    fn test_cancel_username_change() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                Pallet::<Test>::cancel_username_change(RuntimeOrigin::signed(1)),
+                Error::<Test>::NoPendingChange
+            );
+            assert_ok!(Pallet::<Test>::schedule_username_change(
+                RuntimeOrigin::signed(1),
+                b"acme".to_vec(),
+                5
+            ));
+            assert_ok!(Pallet::<Test>::cancel_username_change(
+                RuntimeOrigin::signed(1)
+            ));
+            System::assert_last_event(
+                Event::<Test>::UsernameChangeCancelled {
+                    who: 1,
+                    username: BoundedVec::truncate_from(b"acme".to_vec()),
+                }
+                .into(),
+            );
+
+            // The username is released and nothing happens at the scheduled block
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(2),
+                b"acme".to_vec()
+            ));
+            run_to_block(5);
+            assert_eq!(Usernames::<Test>::get(1), None);
+            assert_eq!(Pallet::<Test>::pending_change(&1), None);
+
+            // A new change can be scheduled for the same account
+            assert_ok!(Pallet::<Test>::schedule_username_change(
+                RuntimeOrigin::signed(1),
+                b"acme2".to_vec(),
+                8
+            ));
+            run_to_block(8);
+            assert_eq!(
+                Usernames::<Test>::get(1),
+                Some(BoundedVec::truncate_from(b"acme2".to_vec()))
+            );
+        });
+    }
+
+    #[test]
+    fn test_moderation_cancels_username_change() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec()
+            ));
+            assert_ok!(Pallet::<Test>::schedule_username_change(
+                RuntimeOrigin::signed(1),
+                b"acme".to_vec(),
+                5
+            ));
+            assert_ok!(Pallet::<Test>::schedule_username_change(
+                RuntimeOrigin::signed(2),
+                b"bad".to_vec(),
+                5
+            ));
+
+            // Clearing the username of an account cancels its scheduled change
+            assert_ok!(Pallet::<Test>::force_clear_username(
+                RuntimeOrigin::root(),
+                1
+            ));
+            System::assert_has_event(
+                Event::<Test>::UsernameChangeCancelled {
+                    who: 1,
+                    username: BoundedVec::truncate_from(b"acme".to_vec()),
+                }
+                .into(),
+            );
+            assert_eq!(Pallet::<Test>::pending_change(&1), None);
+
+            // Blocking a username cancels the change reserving it
+            assert_ok!(Pallet::<Test>::block_username(
+                RuntimeOrigin::root(),
+                b"bad".to_vec()
+            ));
+            System::assert_has_event(
+                Event::<Test>::UsernameChangeCancelled {
+                    who: 2,
+                    username: BoundedVec::truncate_from(b"bad".to_vec()),
+                }
+                .into(),
+            );
+            assert_eq!(Pallet::<Test>::pending_change(&2), None);
+            assert_eq!(ReservedUsernames::<Test>::iter().count(), 0);
+            assert_ok!(Pallet::<Test>::do_try_state());
+
+            // The usernames are released and nothing happens at the scheduled block
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(3),
+                b"acme".to_vec()
+            ));
+            run_to_block(5);
+            assert_eq!(Usernames::<Test>::get(1), None);
+            assert_eq!(Usernames::<Test>::get(2), None);
+            assert!(!System::events().iter().any(|record| matches!(
+                record.event,
+                RuntimeEvent::Scheduler(pallet_scheduler::Event::Dispatched { .. })
+            )));
+        });
+    }
+
+    #[test]
+    fn test_registries_are_independent() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(Pallet::<Test>::set_username(
+                RuntimeOrigin::signed(1),
+                b"robot".to_vec()
+            ));
+            // The same name is free in the other registry, under its own rules
+            assert_noop!(
+                BotUsernames::set_username(RuntimeOrigin::signed(2), b"robots".to_vec()),
+                Error::<Test, Instance1>::UsernameNotAllowed
+            );
+            assert_noop!(
+                BotUsernames::set_username(RuntimeOrigin::signed(2), b"helperbot".to_vec()),
+                Error::<Test, Instance1>::UsernameTooLong
+            );
+            assert_ok!(BotUsernames::set_username(
+                RuntimeOrigin::signed(2),
+                b"robot".to_vec()
+            ));
+            System::assert_last_event(RuntimeEvent::BotUsernames(Event::UsernameSet {
+                who: 2,
+                username: BoundedVec::truncate_from(b"robot".to_vec()),
+            }));
+
+            assert_eq!(Pallet::<Test>::account_of(b"robot"), Some(1));
+            assert_eq!(Pallet::<Test, Instance1>::account_of(b"robot"), Some(2));
+            assert_eq!(Usernames::<Test>::get(2), None);
+            assert_eq!(UsernameCount::<Test>::get(), 1);
+            assert_eq!(UsernameCount::<Test, Instance1>::get(), 1);
+
+            // Only the default registry sponsors registrations
+            assert!(Pallet::<Test>::is_sponsorable(&3, b"carol"));
+            assert!(!Pallet::<Test, Instance1>::is_sponsorable(&3, b"carolbot"));
+
+            // Scheduled changes of the same account do not collide across registries
+            assert_ok!(Pallet::<Test>::schedule_username_change(
+                RuntimeOrigin::signed(1),
+                b"alice".to_vec(),
+                3
+            ));
+            assert_ok!(BotUsernames::schedule_username_change(
+                RuntimeOrigin::signed(1),
+                b"alicebot".to_vec(),
+                3
+            ));
+            run_to_block(3);
+            assert_eq!(Pallet::<Test>::username_of(&1), Some(b"alice".to_vec()));
+            assert_eq!(
+                Pallet::<Test, Instance1>::username_of(&1),
+                Some(b"alicebot".to_vec())
+            );
+            assert_ok!(Pallet::<Test>::do_try_state());
+            assert_ok!(Pallet::<Test, Instance1>::do_try_state());
+        });
+    }
+
+    #[test]
+    fn test_backfill_username_owners_migration() {
+        use frame_support::traits::{GetStorageVersion, OnRuntimeUpgrade, StorageVersion};
+
+        new_test_ext().execute_with(|| {
+            StorageVersion::new(0).put::<Pallet<Test>>();
+            for (who, name) in [(3, b"alice"), (1, b"alice"), (2, b"bobby")] {
+                Usernames::<Test>::insert(who, BoundedVec::try_from(name.to_vec()).unwrap());
+            }
+            // Stale reverse entry, pointing to an account with another username
+            UsernameOwners::<Test>::insert(BoundedVec::try_from(b"carol".to_vec()).unwrap(), 2);
+
+            crate::migrations::v1::BackfillUsernameOwners::<Test>::on_runtime_upgrade();
+
+            // The lowest account keeps a duplicated username
+            assert_eq!(Pallet::<Test>::account_of(b"alice"), Some(1));
+            assert_eq!(Pallet::<Test>::username_of(&3), None);
+            System::assert_has_event(Event::<Test>::UsernameCleared { who: 3 }.into());
+            assert_eq!(Pallet::<Test>::account_of(b"bobby"), Some(2));
+            assert_eq!(Pallet::<Test>::account_of(b"carol"), None);
+            assert_eq!(Pallet::<Test>::on_chain_storage_version(), 1);
+
+            crate::migrations::v2::InitUsernameCount::<Test>::on_runtime_upgrade();
+
+            assert_eq!(UsernameCount::<Test>::get(), 2);
+            assert_eq!(Pallet::<Test>::on_chain_storage_version(), 2);
+            assert_ok!(Pallet::<Test>::do_try_state());
+        });
+    }
+
+    #[test]
+    fn test_init_username_count_migration() {
+        use frame_support::traits::{GetStorageVersion, OnRuntimeUpgrade, StorageVersion};
+
+        new_test_ext().execute_with(|| {
+            StorageVersion::new(1).put::<Pallet<Test>>();
+            for (who, name) in [(1, b"alice"), (2, b"bobby")] {
+                Usernames::<Test>::insert(who, BoundedVec::try_from(name.to_vec()).unwrap());
+            }
+
+            crate::migrations::v2::InitUsernameCount::<Test>::on_runtime_upgrade();
+
+            assert_eq!(UsernameCount::<Test>::get(), 2);
+            assert_eq!(Pallet::<Test>::on_chain_storage_version(), 2);
+        });
+    }
 }
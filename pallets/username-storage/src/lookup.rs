@@ -0,0 +1,42 @@
+use crate::{Config, UsernameOwners};
+use frame_support::{dispatch::DispatchClass, traits::Get, BoundedVec};
+use sp_runtime::{
+    traits::{LookupError, StaticLookup},
+    MultiAddress,
+};
+use sp_std::{convert::TryInto, marker::PhantomData};
+
+/// A [`StaticLookup`] that accepts a username wherever an account is expected.
+///
+/// `MultiAddress::Id` is passed through unchanged and `MultiAddress::Raw` is resolved through the
+/// `UsernameOwners` reverse index. Every other variant is rejected, as with the default
+/// `AccountIdLookup`.
+///
+/// The storage read needed to resolve a username is not part of the weight of the call that
+/// takes the address, so it is registered as extra block weight when the lookup happens.
+pub struct UsernameLookup<T, I = ()>(PhantomData<(T, I)>);
+
+impl<T: Config<I>, I: 'static> StaticLookup for UsernameLookup<T, I> {
+    type Source = MultiAddress<T::AccountId, ()>;
+    type Target = T::AccountId;
+
+    fn lookup(a: Self::Source) -> Result<Self::Target, LookupError> {
+        match a {
+            MultiAddress::Id(who) => Ok(who),
+            MultiAddress::Raw(username) => {
+                frame_system::Pallet::<T>::register_extra_weight_unchecked(
+                    T::DbWeight::get().reads(1),
+                    DispatchClass::Normal,
+                );
+                let username: BoundedVec<u8, T::MaxUsernameLength> =
+                    username.try_into().map_err(|_| LookupError)?;
+                UsernameOwners::<T, I>::get(username).ok_or(LookupError)
+            }
+            _ => Err(LookupError),
+        }
+    }
+
+    fn unlookup(a: Self::Target) -> Self::Source {
+        MultiAddress::Id(a)
+    }
+}
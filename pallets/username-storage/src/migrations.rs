@@ -0,0 +1,125 @@
+//! Storage migrations of the username storage pallet.
+
+use crate::{Config, Event, Pallet, UsernameCount, UsernameOwners, Usernames};
+use frame_support::{
+    migrations::VersionedMigration,
+    traits::{Get, UncheckedOnRuntimeUpgrade},
+    weights::Weight,
+};
+use sp_std::{marker::PhantomData, vec::Vec};
+
+/// Version 1 introduces the `UsernameOwners` reverse index, and with it unique usernames.
+pub mod v1 {
+    use super::*;
+    use crate::OnUsernameChanged;
+
+    /// Fill `UsernameOwners` from the usernames already stored.
+    ///
+    /// Usernames held by several accounts are kept by the lowest account id. The other holders
+    /// lose their username, as if they had cleared it: the change hooks are called and a
+    /// `UsernameCleared` event is deposited. Reverse entries without a matching username are
+    /// removed.
+    pub struct BackfillUsernameOwnersInner<T, I = ()>(PhantomData<(T, I)>);
+
+    impl<T: Config<I>, I: 'static> UncheckedOnRuntimeUpgrade for BackfillUsernameOwnersInner<T, I> {
+        fn on_runtime_upgrade() -> Weight {
+            let mut reads = 0u64;
+            let mut writes = 0u64;
+            let mut duplicates = Vec::new();
+
+            for (who, username) in Usernames::<T, I>::iter() {
+                reads += 3;
+                let owner = UsernameOwners::<T, I>::get(&username)
+                    .filter(|owner| Usernames::<T, I>::get(owner).as_ref() == Some(&username));
+                match owner {
+                    Some(owner) if owner == who => {}
+                    Some(owner) if owner < who => duplicates.push((who, username)),
+                    owner => {
+                        if let Some(owner) = owner {
+                            duplicates.push((owner, username.clone()));
+                        }
+                        UsernameOwners::<T, I>::insert(&username, &who);
+                        writes += 1;
+                    }
+                }
+            }
+
+            for (who, username) in duplicates {
+                Usernames::<T, I>::remove(&who);
+                writes += 1;
+                T::OnUsernameChanged::on_username_changed(&who, Some(&username[..]), None);
+                Pallet::<T, I>::deposit_event(Event::UsernameCleared { who });
+            }
+
+            for (username, owner) in UsernameOwners::<T, I>::iter() {
+                reads += 2;
+                if Usernames::<T, I>::get(&owner).as_ref() != Some(&username) {
+                    UsernameOwners::<T, I>::remove(&username);
+                    writes += 1;
+                }
+            }
+
+            T::DbWeight::get().reads_writes(reads, writes)
+        }
+
+        #[cfg(feature = "try-runtime")]
+        fn post_upgrade(_state: Vec<u8>) -> Result<(), sp_runtime::TryRuntimeError> {
+            for (who, username) in Usernames::<T, I>::iter() {
+                frame_support::ensure!(
+                    UsernameOwners::<T, I>::get(&username).as_ref() == Some(&who),
+                    "Username is not indexed in UsernameOwners"
+                );
+            }
+            for (username, owner) in UsernameOwners::<T, I>::iter() {
+                frame_support::ensure!(
+                    Usernames::<T, I>::get(&owner).as_ref() == Some(&username),
+                    "UsernameOwners entry without a matching username"
+                );
+            }
+            Ok(())
+        }
+    }
+
+    /// [`BackfillUsernameOwnersInner`] run once, when upgrading from storage version 0.
+    pub type BackfillUsernameOwners<T, I = ()> = VersionedMigration<
+        0,
+        1,
+        BackfillUsernameOwnersInner<T, I>,
+        Pallet<T, I>,
+        <T as frame_system::Config>::DbWeight,
+    >;
+}
+
+/// Version 2 introduces the `UsernameCount` counter.
+pub mod v2 {
+    use super::*;
+
+    /// Initialise `UsernameCount` from the usernames already stored.
+    pub struct InitUsernameCountInner<T, I = ()>(PhantomData<(T, I)>);
+
+    impl<T: Config<I>, I: 'static> UncheckedOnRuntimeUpgrade for InitUsernameCountInner<T, I> {
+        fn on_runtime_upgrade() -> Weight {
+            let count = Usernames::<T, I>::iter_keys().count() as u32;
+            UsernameCount::<T, I>::put(count);
+            T::DbWeight::get().reads_writes(count as u64, 1)
+        }
+
+        #[cfg(feature = "try-runtime")]
+        fn post_upgrade(_state: Vec<u8>) -> Result<(), sp_runtime::TryRuntimeError> {
+            frame_support::ensure!(
+                UsernameCount::<T, I>::get() as usize == Usernames::<T, I>::iter_keys().count(),
+                "UsernameCount does not match the stored usernames"
+            );
+            Ok(())
+        }
+    }
+
+    /// [`InitUsernameCountInner`] run once, when upgrading from storage version 1.
+    pub type InitUsernameCount<T, I = ()> = VersionedMigration<
+        1,
+        2,
+        InitUsernameCountInner<T, I>,
+        Pallet<T, I>,
+        <T as frame_system::Config>::DbWeight,
+    >;
+}
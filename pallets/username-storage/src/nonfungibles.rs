@@ -0,0 +1,31 @@
+//! Usernames exposed as non-fungible items.
+//!
+//! All usernames belong to a single collection, identified by `()`, and each username is an item
+//! identified by its bytes. Transferring an item moves the username to the destination account.
+
+use crate::{Config, Pallet, UsernameOwners};
+use frame_support::{
+    pallet_prelude::*,
+    traits::tokens::nonfungibles_v2::{Inspect, Transfer},
+};
+use sp_runtime::TokenError;
+
+impl<T: Config<I>, I: 'static> Inspect<T::AccountId> for Pallet<T, I> {
+    type ItemId = BoundedVec<u8, T::MaxUsernameLength>;
+    type CollectionId = ();
+
+    fn owner(_collection: &Self::CollectionId, item: &Self::ItemId) -> Option<T::AccountId> {
+        UsernameOwners::<T, I>::get(item)
+    }
+}
+
+impl<T: Config<I>, I: 'static> Transfer<T::AccountId> for Pallet<T, I> {
+    fn transfer(
+        collection: &Self::CollectionId,
+        item: &Self::ItemId,
+        destination: &T::AccountId,
+    ) -> DispatchResult {
+        let owner = Self::owner(collection, item).ok_or(TokenError::UnknownAsset)?;
+        Self::do_transfer(&owner, destination)
+    }
+}
@@ -0,0 +1,39 @@
+use sp_std::vec::Vec;
+
+/// Handler for username changes, implemented by pallets that need to react to them.
+///
+/// `old` is `None` when an account registers its first username and `new` is `None` when the
+/// username is removed. Implemented for tuples so several handlers can be plugged in at once.
+#[impl_trait_for_tuples::impl_for_tuples(30)]
+pub trait OnUsernameChanged<AccountId> {
+    /// Called after the username of `who` changed from `old` to `new`.
+    fn on_username_changed(who: &AccountId, old: Option<&[u8]>, new: Option<&[u8]>);
+}
+
+/// Rules a username must follow to be registered, on top of its length.
+///
+/// The unit type accepts every username.
+pub trait UsernameRules {
+    /// Whether `username` can be registered.
+    fn is_allowed(username: &[u8]) -> bool;
+}
+
+impl UsernameRules for () {
+    fn is_allowed(_username: &[u8]) -> bool {
+        true
+    }
+}
+
+/// Read access to registered usernames for other pallets.
+pub trait UsernameProvider<AccountId> {
+    /// The username registered by `who`, if any.
+    fn username_of(who: &AccountId) -> Option<Vec<u8>>;
+
+    /// The account that owns `username`, if any.
+    fn account_of(username: &[u8]) -> Option<AccountId>;
+
+    /// Whether `who` has a username registered.
+    fn has_username(who: &AccountId) -> bool {
+        Self::username_of(who).is_some()
+    }
+}
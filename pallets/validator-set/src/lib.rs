@@ -0,0 +1,316 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Proof-of-authority validator set.
+//!
+//! The validators are a list of accounts kept by this pallet and changed by
+//! `Config::AddRemoveOrigin`. The pallet is the `SessionManager` of `pallet_session`: a change is
+//! handed over at the next session rotation and the new set starts authoring and finalizing one
+//! session later, through the session keys registered by each validator.
+
+pub use pallet::*;
+
+use sp_std::vec::Vec;
+
+#[frame_support::pallet]
+pub mod pallet {
+    use frame_support::{pallet_prelude::*, traits::ValidatorRegistration};
+    use frame_system::pallet_prelude::*;
+    use sp_runtime::traits::StaticLookup;
+    use sp_std::vec::Vec;
+
+    type AccountIdLookupOf<T> = <<T as frame_system::Config>::Lookup as StaticLookup>::Source;
+
+    #[pallet::config]
+    pub trait Config: frame_system::Config {
+        /// The overarching event type.
+        type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+        /// Origin allowed to add and remove validators
+        type AddRemoveOrigin: EnsureOrigin<Self::RuntimeOrigin>;
+        /// Whether an account registered its session keys
+        type ValidatorRegistration: ValidatorRegistration<Self::AccountId>;
+        /// Minimum number of validators, removals below it are rejected
+        #[pallet::constant]
+        type MinValidators: Get<u32>;
+        /// Maximum number of validators
+        #[pallet::constant]
+        type MaxValidators: Get<u32>;
+    }
+
+    #[pallet::pallet]
+    pub struct Pallet<T>(_);
+
+    /// The validators of the upcoming sessions.
+    #[pallet::storage]
+    pub type Validators<T: Config> =
+        StorageValue<_, BoundedVec<T::AccountId, T::MaxValidators>, ValueQuery>;
+
+    /// Whether `Validators` changed since it was last handed to the session pallet.
+    #[pallet::storage]
+    pub type ValidatorsChanged<T: Config> = StorageValue<_, bool, ValueQuery>;
+
+    #[pallet::genesis_config]
+    #[derive(frame_support::DefaultNoBound)]
+    pub struct GenesisConfig<T: Config> {
+        /// Validators of the first session
+        pub validators: Vec<T::AccountId>,
+    }
+
+    #[pallet::genesis_build]
+    impl<T: Config> BuildGenesisConfig for GenesisConfig<T> {
+        fn build(&self) {
+            let mut validators = BoundedVec::<T::AccountId, T::MaxValidators>::default();
+            for who in &self.validators {
+                assert!(!validators.contains(who), "Genesis validators are unique");
+                validators
+                    .try_push(who.clone())
+                    .expect("Genesis validators fit in MaxValidators");
+            }
+            assert!(
+                validators.len() as u32 >= T::MinValidators::get(),
+                "Genesis has at least MinValidators validators"
+            );
+            Validators::<T>::put(validators);
+        }
+    }
+
+    #[pallet::event]
+    #[pallet::generate_deposit(pub(super) fn deposit_event)]
+    pub enum Event<T: Config> {
+        /// Validator was added, from the session after next
+        ValidatorAdded {
+            /// The new validator
+            who: T::AccountId,
+        },
+        /// Validator was removed, from the session after next
+        ValidatorRemoved {
+            /// The removed validator
+            who: T::AccountId,
+        },
+    }
+
+    #[pallet::error]
+    pub enum Error<T> {
+        /// Account is already a validator
+        AlreadyValidator,
+        /// Account is not a validator
+        NotValidator,
+        /// The validator set is full
+        TooManyValidators,
+        /// Removing the validator would leave fewer than `MinValidators`
+        TooFewValidators,
+        /// Account has not registered its session keys
+        NoSessionKeys,
+    }
+
+    #[pallet::call]
+    impl<T: Config> Pallet<T> {
+        /// Add `who` to the validators.
+        ///
+        /// `who` must have registered its session keys with `session.setKeys` beforehand.
+        #[pallet::call_index(0)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(2, 2))]
+        pub fn add_validator(origin: OriginFor<T>, who: AccountIdLookupOf<T>) -> DispatchResult {
+            T::AddRemoveOrigin::ensure_origin(origin)?;
+            let who = T::Lookup::lookup(who)?;
+            ensure!(
+                T::ValidatorRegistration::is_registered(&who),
+                Error::<T>::NoSessionKeys
+            );
+
+            Validators::<T>::try_mutate(|validators| {
+                ensure!(!validators.contains(&who), Error::<T>::AlreadyValidator);
+                validators
+                    .try_push(who.clone())
+                    .map_err(|_| Error::<T>::TooManyValidators)
+            })?;
+            ValidatorsChanged::<T>::put(true);
+            Self::deposit_event(Event::ValidatorAdded { who });
+
+            Ok(())
+        }
+
+        /// Remove `who` from the validators.
+        #[pallet::call_index(1)]
+        #[pallet::weight(T::DbWeight::get().reads_writes(1, 2))]
+        pub fn remove_validator(origin: OriginFor<T>, who: AccountIdLookupOf<T>) -> DispatchResult {
+            T::AddRemoveOrigin::ensure_origin(origin)?;
+            let who = T::Lookup::lookup(who)?;
+
+            Validators::<T>::try_mutate(|validators| {
+                let index = validators
+                    .iter()
+                    .position(|v| v == &who)
+                    .ok_or(Error::<T>::NotValidator)?;
+                ensure!(
+                    validators.len() as u32 > T::MinValidators::get(),
+                    Error::<T>::TooFewValidators
+                );
+                validators.remove(index);
+                Ok::<_, Error<T>>(())
+            })?;
+            ValidatorsChanged::<T>::put(true);
+            Self::deposit_event(Event::ValidatorRemoved { who });
+
+            Ok(())
+        }
+    }
+}
+
+impl<T: Config> pallet_session::SessionManager<T::AccountId> for Pallet<T> {
+    fn new_session(_new_index: u32) -> Option<Vec<T::AccountId>> {
+        if ValidatorsChanged::<T>::take() {
+            Some(Validators::<T>::get().into_inner())
+        } else {
+            None
+        }
+    }
+
+    fn new_session_genesis(_new_index: u32) -> Option<Vec<T::AccountId>> {
+        Some(Validators::<T>::get().into_inner())
+    }
+
+    fn end_session(_end_index: u32) {}
+
+    fn start_session(_start_index: u32) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_support::{
+        assert_noop, assert_ok, derive_impl, parameter_types,
+        traits::{ConstU32, ValidatorRegistration},
+    };
+    use frame_system::EnsureRoot;
+    use pallet_session::SessionManager;
+    use sp_runtime::{BuildStorage, DispatchError};
+
+    type Block = frame_system::mocking::MockBlock<Test>;
+
+    #[frame_support::runtime]
+    mod runtime {
+        #[runtime::runtime]
+        #[runtime::derive(
+            RuntimeCall,
+            RuntimeEvent,
+            RuntimeError,
+            RuntimeOrigin,
+            RuntimeFreezeReason,
+            RuntimeHoldReason,
+            RuntimeSlashReason,
+            RuntimeLockId,
+            RuntimeTask
+        )]
+        pub struct Test;
+
+        #[runtime::pallet_index(0)]
+        pub type System = frame_system;
+
+        #[runtime::pallet_index(1)]
+        pub type ValidatorSet = crate::pallet;
+    }
+
+    #[derive_impl(frame_system::config_preludes::TestDefaultConfig)]
+    impl frame_system::Config for Test {
+        type Block = Block;
+    }
+
+    parameter_types! {
+        pub static Registered: Vec<u64> = vec![1, 2, 3, 4];
+    }
+
+    pub struct MockRegistration;
+    impl ValidatorRegistration<u64> for MockRegistration {
+        fn is_registered(id: &u64) -> bool {
+            Registered::get().contains(id)
+        }
+    }
+
+    impl Config for Test {
+        type RuntimeEvent = RuntimeEvent;
+        type AddRemoveOrigin = EnsureRoot<u64>;
+        type ValidatorRegistration = MockRegistration;
+        type MinValidators = ConstU32<1>;
+        type MaxValidators = ConstU32<3>;
+    }
+
+    fn new_test_ext() -> sp_io::TestExternalities {
+        let mut t = frame_system::GenesisConfig::<Test>::default()
+            .build_storage()
+            .unwrap();
+        GenesisConfig::<Test> {
+            validators: vec![1],
+        }
+        .assimilate_storage(&mut t)
+        .unwrap();
+        let mut ext = sp_io::TestExternalities::new(t);
+        ext.execute_with(|| System::set_block_number(1));
+        ext
+    }
+
+    fn validators() -> Vec<u64> {
+        Validators::<Test>::get().into_inner()
+    }
+
+    #[test]
+    fn test_genesis_session() {
+        new_test_ext().execute_with(|| {
+            assert_eq!(ValidatorSet::new_session_genesis(0), Some(vec![1]));
+            // Nothing changed since genesis
+            assert_eq!(ValidatorSet::new_session(1), None);
+        });
+    }
+
+    #[test]
+    fn test_add_validator() {
+        new_test_ext().execute_with(|| {
+            assert_noop!(
+                ValidatorSet::add_validator(RuntimeOrigin::signed(1), 2),
+                DispatchError::BadOrigin
+            );
+            assert_ok!(ValidatorSet::add_validator(RuntimeOrigin::root(), 2));
+            System::assert_last_event(Event::ValidatorAdded { who: 2 }.into());
+            assert_eq!(validators(), vec![1, 2]);
+
+            // The change is handed to the session pallet once
+            assert_eq!(ValidatorSet::new_session(1), Some(vec![1, 2]));
+            assert_eq!(ValidatorSet::new_session(2), None);
+
+            assert_noop!(
+                ValidatorSet::add_validator(RuntimeOrigin::root(), 2),
+                Error::<Test>::AlreadyValidator
+            );
+            assert_noop!(
+                ValidatorSet::add_validator(RuntimeOrigin::root(), 5),
+                Error::<Test>::NoSessionKeys
+            );
+            assert_ok!(ValidatorSet::add_validator(RuntimeOrigin::root(), 3));
+            assert_noop!(
+                ValidatorSet::add_validator(RuntimeOrigin::root(), 4),
+                Error::<Test>::TooManyValidators
+            );
+        });
+    }
+
+    #[test]
+    fn test_remove_validator() {
+        new_test_ext().execute_with(|| {
+            assert_ok!(ValidatorSet::add_validator(RuntimeOrigin::root(), 2));
+            assert_eq!(ValidatorSet::new_session(1), Some(vec![1, 2]));
+
+            assert_noop!(
+                ValidatorSet::remove_validator(RuntimeOrigin::root(), 3),
+                Error::<Test>::NotValidator
+            );
+            assert_ok!(ValidatorSet::remove_validator(RuntimeOrigin::root(), 1));
+            System::assert_last_event(Event::ValidatorRemoved { who: 1 }.into());
+            assert_eq!(ValidatorSet::new_session(2), Some(vec![2]));
+
+            // The last validator cannot be removed
+            assert_noop!(
+                ValidatorSet::remove_validator(RuntimeOrigin::root(), 2),
+                Error::<Test>::TooFewValidators
+            );
+        });
+    }
+}
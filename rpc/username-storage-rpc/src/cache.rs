@@ -0,0 +1,143 @@
+//! Cache of username lookups.
+//!
+//! Entries are keyed by `(block hash, account)`, and the state of a block never changes, so a
+//! cached entry stays correct whatever happens to the chain, re-orgs included. To keep hot
+//! entries useful as the chain grows, the cache remembers the parent of recent best blocks and
+//! the accounts whose username changed in them. A lookup that misses at a block is served by
+//! the entry of an ancestor, as long as the username of the account did not change in between.
+
+use crate::subscription::{usernames_prefix, DEFAULT_PALLET};
+use futures::{FutureExt, StreamExt};
+use parking_lot::Mutex;
+use sc_client_api::BlockchainEvents;
+use sc_rpc::SubscriptionTaskExecutor;
+use schnellru::{ByLength, LruMap};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
+use std::sync::Arc;
+
+/// Default number of entries kept by the cache.
+pub const DEFAULT_CACHE_SIZE: u32 = 4096;
+
+/// Number of recent best blocks whose username changes are remembered.
+const TRACKED_BLOCKS: u32 = 256;
+
+/// Least recently used cache of usernames, keyed by block hash and encoded account.
+pub(crate) struct UsernameCache<Hash: std::hash::Hash + PartialEq> {
+    entries: Mutex<LruMap<(Hash, Vec<u8>), Option<Vec<u8>>, ByLength>>,
+    /// Parent of recent best blocks, with the encoded accounts whose username changed in them.
+    blocks: Mutex<LruMap<Hash, (Hash, Vec<Vec<u8>>), ByLength>>,
+}
+
+impl<Hash: Copy + Eq + std::hash::Hash> UsernameCache<Hash> {
+    pub(crate) fn new(size: u32) -> Self {
+        Self {
+            entries: Mutex::new(LruMap::new(ByLength::new(size))),
+            blocks: Mutex::new(LruMap::new(ByLength::new(TRACKED_BLOCKS))),
+        }
+    }
+
+    /// The cached username of `account` at `at`, `None` when it is not cached.
+    pub(crate) fn get(&self, at: Hash, account: &[u8]) -> Option<Option<Vec<u8>>> {
+        let key = (at, account.to_vec());
+        let mut entries = self.entries.lock();
+        if let Some(username) = entries.get(&key) {
+            return Some(username.clone());
+        }
+
+        // Walk up the tracked ancestors of `at` until one has an entry for `account`, giving up
+        // at a block that changed its username.
+        let blocks = self.blocks.lock();
+        let mut hash = at;
+        let username = loop {
+            let (parent, changed) = blocks.peek(&hash)?;
+            if changed.iter().any(|changed| changed == account) {
+                return None;
+            }
+            hash = *parent;
+            if let Some(username) = entries.get(&(hash, account.to_vec())) {
+                break username.clone();
+            }
+        };
+
+        entries.insert(key, username.clone());
+        Some(username)
+    }
+
+    pub(crate) fn insert(&self, at: Hash, account: Vec<u8>, username: Option<Vec<u8>>) {
+        self.entries.lock().insert((at, account), username);
+    }
+
+    /// Record that `hash` is a child of `parent` changing the username of the `changed` accounts.
+    fn advance(&self, parent: Hash, hash: Hash, changed: Vec<Vec<u8>>) {
+        self.blocks.lock().insert(hash, (parent, changed));
+    }
+}
+
+/// Keep `cache` in step with new best blocks until the node shuts down.
+pub(crate) fn spawn_follower<C, Block>(
+    executor: &SubscriptionTaskExecutor,
+    client: Arc<C>,
+    cache: Arc<UsernameCache<Block::Hash>>,
+) where
+    Block: BlockT,
+    C: BlockchainEvents<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+{
+    // Storage notifications can only be filtered by exact keys, and the accounts are not known
+    // in advance, so the `Usernames` prefix is matched here. Every best block changes some key,
+    // which tells the cache about each of them.
+    let Ok(mut notifications) = client.storage_changes_notification_stream(None, None) else {
+        log::warn!(
+            target: "rpc",
+            "Unable to follow storage changes, username cache entries will not be carried over"
+        );
+        return;
+    };
+
+    let prefix = usernames_prefix(DEFAULT_PALLET);
+    let follower = async move {
+        while let Some(notification) = notifications.next().await {
+            let Ok(Some(header)) = client.header(notification.block) else {
+                continue;
+            };
+
+            // Keys of `Usernames` end with `blake2_128(account) ++ account`.
+            let changed: Vec<Vec<u8>> = notification
+                .changes
+                .iter()
+                .filter(|(child, _, _)| child.is_none())
+                .filter_map(|(_, key, _)| key.0.strip_prefix(&prefix[..])?.get(16..))
+                .map(<[u8]>::to_vec)
+                .collect();
+            cache.advance(*header.parent_hash(), notification.block, changed);
+        }
+    };
+
+    executor.spawn("username-storage-cache", Some("rpc"), follower.boxed());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_served_by_ancestor() {
+        let cache = UsernameCache::<u64>::new(16);
+        cache.insert(1, b"alice".to_vec(), Some(b"acme".to_vec()));
+        cache.insert(1, b"bob".to_vec(), None);
+
+        // Block 2 changes the username of bob, block 3 changes nothing
+        cache.advance(1, 2, vec![b"bob".to_vec()]);
+        cache.advance(2, 3, Vec::new());
+        assert_eq!(cache.get(3, b"alice"), Some(Some(b"acme".to_vec())));
+        assert_eq!(cache.get(3, b"bob"), None);
+        assert_eq!(cache.get(3, b"charlie"), None);
+
+        // A fork of block 1 does not see the changes of block 2
+        cache.advance(1, 12, Vec::new());
+        assert_eq!(cache.get(12, b"bob"), Some(None));
+
+        // Blocks the cache was not told about are never served
+        assert_eq!(cache.get(4, b"alice"), None);
+    }
+}
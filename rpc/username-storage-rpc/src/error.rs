@@ -0,0 +1,304 @@
+//! Errors returned by the username storage RPC.
+//!
+//! Every error carries a stable code that clients can match on, the message is only meant for
+//! humans. The `data` field holds a JSON object with the details documented on each variant.
+//!
+//! | Code | Error |
+//! |------|-------|
+//! | 1000 | [`Error::RuntimeApi`] |
+//! | 1001 | [`Error::UnsupportedApi`] |
+//! | 1002 | [`Error::UnknownBlock`] |
+//! | 1003 | [`Error::StatePruned`] |
+//! | 1004 | [`Error::TooManyAccounts`] |
+//! | 1005 | [`Error::Subscription`] |
+//! | 1006 | [`Error::Dispatch`] |
+//! | 1007 | [`Error::Indexer`] |
+//! | 1008 | [`Error::UnknownRegistry`] |
+//! | 1100 - 1199 | [`Error::Pallet`], one code per [`PalletError`] |
+
+use jsonrpsee::types::error::{ErrorObject, ErrorObjectOwned};
+use serde_json::json;
+use solochain_template_runtime::{Runtime, RuntimeError};
+use sp_runtime::DispatchError;
+
+/// A call of the runtime API failed.
+pub const RUNTIME_API_ERROR: i32 = 1000;
+/// The runtime does not implement the required version of the runtime API.
+pub const UNSUPPORTED_API_ERROR: i32 = 1001;
+/// The requested block is not known.
+pub const UNKNOWN_BLOCK_ERROR: i32 = 1002;
+/// The state of the requested block was pruned.
+pub const STATE_PRUNED_ERROR: i32 = 1003;
+/// The request names more accounts than allowed.
+pub const TOO_MANY_ACCOUNTS_ERROR: i32 = 1004;
+/// A subscription could not be set up.
+pub const SUBSCRIPTION_ERROR: i32 = 1005;
+/// A call failed with an error that does not come from the username storage pallet.
+pub const DISPATCH_ERROR: i32 = 1006;
+/// The username event index failed to answer a query.
+pub const INDEXER_ERROR: i32 = 1007;
+/// The runtime has no username registry with the requested name.
+pub const UNKNOWN_REGISTRY_ERROR: i32 = 1008;
+
+/// Errors of the username storage RPC.
+#[derive(Debug)]
+pub enum Error {
+    /// A runtime API call failed.
+    ///
+    /// `data`: `{ "reason": string }`
+    RuntimeApi(String),
+    /// The runtime at the requested block does not implement the required API version.
+    ///
+    /// `data`: `{ "required": number, "found": number | null }`, `found` is `null` when the API
+    /// is missing altogether.
+    UnsupportedApi { required: u32, found: Option<u32> },
+    /// The requested block is not known.
+    ///
+    /// `data`: `{ "block": string }`
+    UnknownBlock(String),
+    /// The state of the requested block was pruned.
+    ///
+    /// `data`: `{ "block": string }`
+    StatePruned(String),
+    /// The request names more accounts than allowed.
+    ///
+    /// `data`: `{ "requested": number, "max": number }`
+    TooManyAccounts { requested: usize, max: usize },
+    /// A subscription could not be set up.
+    ///
+    /// `data`: `{ "reason": string }`
+    Subscription(String),
+    /// The username storage pallet rejected the call.
+    ///
+    /// `data`: `{ "error": string }` with the name of the pallet error.
+    Pallet(PalletError),
+    /// The call failed with an error that does not come from the username storage pallet.
+    ///
+    /// `data`: `{ "reason": string }`
+    Dispatch(String),
+    /// The username event index of the node failed to answer a query.
+    ///
+    /// `data`: `{ "reason": string }`
+    Indexer(String),
+    /// The runtime has no username registry with the requested name.
+    ///
+    /// `data`: `{ "registry": string }`
+    UnknownRegistry(String),
+}
+
+impl Error {
+    /// Build the error for a failed dispatch, keeping errors of the pallet apart.
+    pub fn from_dispatch_error(error: DispatchError) -> Self {
+        match PalletError::from_dispatch_error(error) {
+            Some(error) => Self::Pallet(error),
+            None => Self::Dispatch(format!("{:?}", error)),
+        }
+    }
+
+    /// The stable code of the error.
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::RuntimeApi(_) => RUNTIME_API_ERROR,
+            Self::UnsupportedApi { .. } => UNSUPPORTED_API_ERROR,
+            Self::UnknownBlock(_) => UNKNOWN_BLOCK_ERROR,
+            Self::StatePruned(_) => STATE_PRUNED_ERROR,
+            Self::TooManyAccounts { .. } => TOO_MANY_ACCOUNTS_ERROR,
+            Self::Subscription(_) => SUBSCRIPTION_ERROR,
+            Self::Pallet(error) => error.code(),
+            Self::Dispatch(_) => DISPATCH_ERROR,
+            Self::Indexer(_) => INDEXER_ERROR,
+            Self::UnknownRegistry(_) => UNKNOWN_REGISTRY_ERROR,
+        }
+    }
+
+    /// Human readable description of the error.
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::RuntimeApi(_) => "Runtime API call failed",
+            Self::UnsupportedApi { found: None, .. } => "Runtime API not available",
+            Self::UnsupportedApi { .. } => "Runtime API version not supported",
+            Self::UnknownBlock(_) => "Unknown block",
+            Self::StatePruned(_) => "State already pruned",
+            Self::TooManyAccounts { .. } => "Too many accounts",
+            Self::Subscription(_) => "Unable to subscribe",
+            Self::Pallet(error) => error.message(),
+            Self::Dispatch(_) => "Dispatch failed",
+            Self::Indexer(_) => "Event index unavailable",
+            Self::UnknownRegistry(_) => "Unknown username registry",
+        }
+    }
+
+    fn data(&self) -> serde_json::Value {
+        match self {
+            Self::RuntimeApi(reason)
+            | Self::Subscription(reason)
+            | Self::Dispatch(reason)
+            | Self::Indexer(reason) => json!({ "reason": reason }),
+            Self::UnsupportedApi { required, found } => {
+                json!({ "required": required, "found": found })
+            }
+            Self::UnknownBlock(block) | Self::StatePruned(block) => json!({ "block": block }),
+            Self::UnknownRegistry(registry) => json!({ "registry": registry }),
+            Self::TooManyAccounts { requested, max } => {
+                json!({ "requested": requested, "max": max })
+            }
+            Self::Pallet(error) => json!({ "error": error.name() }),
+        }
+    }
+}
+
+impl From<Error> for ErrorObjectOwned {
+    fn from(error: Error) -> Self {
+        ErrorObject::owned(error.code(), error.message(), Some(error.data()))
+    }
+}
+
+impl From<sp_api::ApiError> for Error {
+    fn from(error: sp_api::ApiError) -> Self {
+        Self::RuntimeApi(format!("{:?}", error))
+    }
+}
+
+/// Errors of `pallet_username_storage`, with their stable RPC codes.
+///
+/// The codes are the same whatever the registry, that is the instance of the pallet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PalletError {
+    UsernameTooLong = 1100,
+    UsernameEmpty = 1101,
+    NoUsername = 1102,
+    UsernameTaken = 1103,
+    DestinationHasUsername = 1104,
+    NotListed = 1105,
+    CannotBuyOwnUsername = 1106,
+    UsernameBlocked = 1107,
+    AlreadyBlocked = 1108,
+    NotBlocked = 1109,
+    UsernameReserved = 1110,
+    ChangeAlreadyScheduled = 1111,
+    ChangeNotInFuture = 1112,
+    ChangeTooFarAhead = 1113,
+    NoPendingChange = 1114,
+    ChangeNotDue = 1115,
+    UsernameNotAllowed = 1116,
+    PriceTooHigh = 1117,
+}
+
+impl PalletError {
+    /// Every pallet error, in code order.
+    pub const ALL: [PalletError; 18] = [
+        Self::UsernameTooLong,
+        Self::UsernameEmpty,
+        Self::NoUsername,
+        Self::UsernameTaken,
+        Self::DestinationHasUsername,
+        Self::NotListed,
+        Self::CannotBuyOwnUsername,
+        Self::UsernameBlocked,
+        Self::AlreadyBlocked,
+        Self::NotBlocked,
+        Self::UsernameReserved,
+        Self::ChangeAlreadyScheduled,
+        Self::ChangeNotInFuture,
+        Self::ChangeTooFarAhead,
+        Self::NoPendingChange,
+        Self::ChangeNotDue,
+        Self::UsernameNotAllowed,
+        Self::PriceTooHigh,
+    ];
+
+    /// The pallet error behind `error`, if it comes from one of the username registries.
+    pub fn from_dispatch_error(error: DispatchError) -> Option<Self> {
+        match RuntimeError::from_dispatch_error(error)? {
+            RuntimeError::UsernameStorage(error) => Self::from_pallet_error(error),
+            RuntimeError::BotUsernames(error) => Self::from_pallet_error(error),
+            RuntimeError::OrganizationUsernames(error) => Self::from_pallet_error(error),
+            _ => None,
+        }
+    }
+
+    fn from_pallet_error<I: 'static>(
+        error: pallet_username_storage::Error<Runtime, I>,
+    ) -> Option<Self> {
+        use pallet_username_storage::Error as E;
+
+        Some(match error {
+            E::UsernameTooLong => Self::UsernameTooLong,
+            E::UsernameEmpty => Self::UsernameEmpty,
+            E::NoUsername => Self::NoUsername,
+            E::UsernameTaken => Self::UsernameTaken,
+            E::DestinationHasUsername => Self::DestinationHasUsername,
+            E::NotListed => Self::NotListed,
+            E::CannotBuyOwnUsername => Self::CannotBuyOwnUsername,
+            E::UsernameBlocked => Self::UsernameBlocked,
+            E::AlreadyBlocked => Self::AlreadyBlocked,
+            E::NotBlocked => Self::NotBlocked,
+            E::UsernameReserved => Self::UsernameReserved,
+            E::ChangeAlreadyScheduled => Self::ChangeAlreadyScheduled,
+            E::ChangeNotInFuture => Self::ChangeNotInFuture,
+            E::ChangeTooFarAhead => Self::ChangeTooFarAhead,
+            E::NoPendingChange => Self::NoPendingChange,
+            E::ChangeNotDue => Self::ChangeNotDue,
+            E::UsernameNotAllowed => Self::UsernameNotAllowed,
+            E::PriceTooHigh => Self::PriceTooHigh,
+            _ => return None,
+        })
+    }
+
+    /// The stable code of the error.
+    pub fn code(&self) -> i32 {
+        *self as i32
+    }
+
+    /// The name of the error variant in the pallet.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UsernameTooLong => "UsernameTooLong",
+            Self::UsernameEmpty => "UsernameEmpty",
+            Self::NoUsername => "NoUsername",
+            Self::UsernameTaken => "UsernameTaken",
+            Self::DestinationHasUsername => "DestinationHasUsername",
+            Self::NotListed => "NotListed",
+            Self::CannotBuyOwnUsername => "CannotBuyOwnUsername",
+            Self::UsernameBlocked => "UsernameBlocked",
+            Self::AlreadyBlocked => "AlreadyBlocked",
+            Self::NotBlocked => "NotBlocked",
+            Self::UsernameReserved => "UsernameReserved",
+            Self::ChangeAlreadyScheduled => "ChangeAlreadyScheduled",
+            Self::ChangeNotInFuture => "ChangeNotInFuture",
+            Self::ChangeTooFarAhead => "ChangeTooFarAhead",
+            Self::NoPendingChange => "NoPendingChange",
+            Self::ChangeNotDue => "ChangeNotDue",
+            Self::UsernameNotAllowed => "UsernameNotAllowed",
+            Self::PriceTooHigh => "PriceTooHigh",
+        }
+    }
+
+    /// Human readable description of the error.
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::UsernameTooLong => "Username is too long",
+            Self::UsernameEmpty => "Username is empty",
+            Self::NoUsername => "Account has no username",
+            Self::UsernameTaken => "Username is already owned by another account",
+            Self::DestinationHasUsername => "Destination account already has a username",
+            Self::NotListed => "Username is not listed for sale",
+            Self::CannotBuyOwnUsername => "Account cannot buy its own username",
+            Self::UsernameBlocked => "Username is on the blocklist",
+            Self::AlreadyBlocked => "Username is already on the blocklist",
+            Self::NotBlocked => "Username is not on the blocklist",
+            Self::UsernameReserved => {
+                "Username is reserved by a scheduled change of another account"
+            }
+            Self::ChangeAlreadyScheduled => "Account already has a scheduled username change",
+            Self::ChangeNotInFuture => "Scheduled change block is not in the future",
+            Self::ChangeTooFarAhead => {
+                "Scheduled change block is more than `MaxChangeDelay` blocks ahead"
+            }
+            Self::NoPendingChange => "Account has no scheduled username change",
+            Self::ChangeNotDue => "Scheduled username change is not due yet",
+            Self::UsernameNotAllowed => "Username does not follow the rules of the registry",
+            Self::PriceTooHigh => "Asking price is above the maximum price of the buyer",
+        }
+    }
+}
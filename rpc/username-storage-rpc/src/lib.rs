@@ -1,80 +1,589 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
-use codec::Codec;
-use jsonrpsee::{
-    core::RpcResult,
-    proc_macros::rpc,
-    types::error::{ErrorCode, ErrorObject},
-};
+use codec::{Codec, Encode};
+use jsonrpsee::{core::RpcResult, proc_macros::rpc, PendingSubscriptionSink};
 use pallet_username_storage::UsernameStorageApi;
-use sp_api::ProvideRuntimeApi;
+use sc_client_api::{BlockBackend, BlockchainEvents};
+use sc_rpc::SubscriptionTaskExecutor;
+use serde::{Deserialize, Serialize};
+use sp_api::{ApiExt, ProvideRuntimeApi};
 use sp_blockchain::HeaderBackend;
+use sp_consensus::BlockStatus;
 use sp_runtime::traits::Block as BlockT;
 use sp_std::sync::Arc;
+use std::time::Instant;
 
+mod cache;
+pub mod error;
+mod metrics;
+pub mod openrpc;
+mod subscription;
+
+pub use cache::DEFAULT_CACHE_SIZE;
+pub use error::{Error, PalletError};
+pub use metrics::RpcMetrics;
+pub use openrpc::{Discover, DiscoverApiServer};
+pub use subscription::UsernameChange;
+
+/// Default maximum number of accounts a single username subscription can watch.
+pub const DEFAULT_MAX_SUBSCRIPTION_ACCOUNTS: usize = 128;
+
+/// Default maximum number of accounts in a single `usernameStorage_getUsernames` call.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 256;
+
+/// Version of `UsernameStorageApi` serving registries other than the default one.
+const REGISTRY_API_VERSION: u32 = 6;
+
+/// Version of `UsernameStorageApi` naming the pallet instance of each registry, which
+/// subscriptions to registries other than the default one need.
+const REGISTRY_PALLET_API_VERSION: u32 = 7;
+
+/// A username as returned by the RPC.
+///
+/// Names that are valid UTF-8 are returned as plain strings. Any other name is returned as
+/// `{ "raw": "0x..." }` with its hex encoded bytes, so it reaches the client unaltered.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Username {
+    /// A valid UTF-8 name
+    Utf8(String),
+    /// The raw bytes of a name that is not valid UTF-8
+    Raw { raw: sp_core::Bytes },
+}
+
+impl Username {
+    /// The bytes of the name as stored on chain.
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Self::Utf8(name) => name.into_bytes(),
+            Self::Raw { raw } => raw.0,
+        }
+    }
+}
+
+impl From<Vec<u8>> for Username {
+    fn from(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(name) => Self::Utf8(name),
+            Err(e) => Self::Raw {
+                raw: e.into_bytes().into(),
+            },
+        }
+    }
+}
+
+/// Username storage RPC methods.
+///
+/// Every method reads the state at `at` when it is given. Otherwise it reads the finalized state
+/// when `finalized` is `true`, and the best block state by default. Failures are reported with the
+/// codes documented in [`error`].
+///
+/// Methods taking a `registry` read the username registry with that name, as listed by
+/// `usernameStorage_registries`, and the default one when it is not given.
 #[rpc(server, client)]
-pub trait UsernameStorageRpcApi<AccountId> {
+pub trait UsernameStorageRpcApi<AccountId, BlockHash> {
     #[method(name = "usernameStorage_getUsername")]
-    fn get_username(&self, account_id: AccountId) -> RpcResult<Option<String>>;
+    fn get_username(
+        &self,
+        account_id: AccountId,
+        at: Option<BlockHash>,
+        finalized: Option<bool>,
+        registry: Option<String>,
+    ) -> RpcResult<Option<Username>>;
+
+    /// The usernames of `account_ids`, in the same order, with `null` for accounts without one.
+    #[method(name = "usernameStorage_getUsernames")]
+    fn get_usernames(
+        &self,
+        account_ids: Vec<AccountId>,
+        at: Option<BlockHash>,
+        finalized: Option<bool>,
+        registry: Option<String>,
+    ) -> RpcResult<Vec<Option<Username>>>;
 
     #[method(name = "usernameStorage_setUsername")]
-    fn set_username(&self, account_id: AccountId, username: String) -> RpcResult<()>;
+    fn set_username(
+        &self,
+        account_id: AccountId,
+        username: String,
+        at: Option<BlockHash>,
+        finalized: Option<bool>,
+        registry: Option<String>,
+    ) -> RpcResult<()>;
+
+    /// Names of the username registries, the default one first.
+    #[method(name = "usernameStorage_registries")]
+    fn registries(&self, at: Option<BlockHash>, finalized: Option<bool>) -> RpcResult<Vec<String>>;
+
+    /// Watch the usernames of `accounts`, pushing a notification every time one of them changes.
+    ///
+    /// Changes are reported on new best blocks, or only on finalized blocks when `finalized` is
+    /// `true`.
+    #[subscription(
+        name = "usernameStorage_subscribeUsername" => "usernameStorage_username",
+        unsubscribe = "usernameStorage_unsubscribeUsername",
+        item = UsernameChange<AccountId, BlockHash>
+    )]
+    fn subscribe_username(
+        &self,
+        accounts: Vec<AccountId>,
+        finalized: Option<bool>,
+        registry: Option<String>,
+    );
 }
 
-pub struct UsernameStorageRpc<C, Block> {
+pub struct UsernameStorageRpc<C, Block: BlockT> {
     client: Arc<C>,
+    executor: SubscriptionTaskExecutor,
+    max_subscription_accounts: usize,
+    max_batch_size: usize,
+    metrics: Option<RpcMetrics>,
+    cache: Option<Arc<cache::UsernameCache<Block::Hash>>>,
     _marker: std::marker::PhantomData<Block>,
 }
 
-impl<C, Block> UsernameStorageRpc<C, Block> {
-    pub fn new(client: Arc<C>) -> Self {
+impl<C, Block: BlockT> UsernameStorageRpc<C, Block> {
+    pub fn new(client: Arc<C>, executor: SubscriptionTaskExecutor) -> Self {
         Self {
             client,
+            executor,
+            max_subscription_accounts: DEFAULT_MAX_SUBSCRIPTION_ACCOUNTS,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            metrics: None,
+            cache: None,
             _marker: Default::default(),
         }
     }
+
+    /// Limit the number of accounts a single subscription can watch.
+    pub fn with_max_subscription_accounts(mut self, max: usize) -> Self {
+        self.max_subscription_accounts = max;
+        self
+    }
+
+    /// Limit the number of accounts a single `usernameStorage_getUsernames` call can look up.
+    pub fn with_max_batch_size(mut self, max: usize) -> Self {
+        self.max_batch_size = max;
+        self
+    }
+
+    /// Record call counts, latencies and errors in `metrics`.
+    pub fn with_metrics(mut self, metrics: RpcMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    fn measure<T>(&self, method: &str, call: impl FnOnce() -> RpcResult<T>) -> RpcResult<T> {
+        let started = Instant::now();
+        let result = call();
+        if let Some(metrics) = &self.metrics {
+            metrics.observe(
+                method,
+                started.elapsed(),
+                result.as_ref().err().map(|e| e.code()),
+            );
+        }
+        result
+    }
+
+    /// Look the usernames of `accounts` up in the cache, fetching the missing ones with `fetch`.
+    fn cached<AccountId: Encode>(
+        &self,
+        at: Block::Hash,
+        accounts: Vec<AccountId>,
+        fetch: impl FnOnce(Vec<AccountId>) -> RpcResult<Vec<Option<Vec<u8>>>>,
+    ) -> RpcResult<Vec<Option<Vec<u8>>>> {
+        let Some(cache) = &self.cache else {
+            return fetch(accounts);
+        };
+
+        let keys: Vec<Vec<u8>> = accounts.iter().map(Encode::encode).collect();
+        let mut usernames: Vec<_> = keys.iter().map(|key| cache.get(at, key)).collect();
+        let missing: Vec<AccountId> = accounts
+            .into_iter()
+            .zip(&usernames)
+            .filter(|(_, username)| username.is_none())
+            .map(|(account, _)| account)
+            .collect();
+        if let Some(metrics) = &self.metrics {
+            metrics.observe_cache(usernames.len() - missing.len(), missing.len());
+        }
+
+        if !missing.is_empty() {
+            let mut fetched = fetch(missing)?.into_iter();
+            for (key, username) in keys.into_iter().zip(usernames.iter_mut()) {
+                if username.is_none() {
+                    let value = fetched.next().ok_or_else(|| {
+                        Error::RuntimeApi("Runtime returned fewer usernames than requested".into())
+                    })?;
+                    cache.insert(at, key, value.clone());
+                    *username = Some(value);
+                }
+            }
+        }
+
+        Ok(usernames.into_iter().map(Option::flatten).collect())
+    }
 }
 
-impl<C, Block, AccountId> UsernameStorageRpcApiServer<AccountId> for UsernameStorageRpc<C, Block>
+impl<C, Block> UsernameStorageRpc<C, Block>
 where
     Block: BlockT,
-    C: Send + Sync + 'static + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
-    C::Api: UsernameStorageApi<Block, AccountId>,
-    AccountId: Codec + Send + Sync + 'static,
+    C: BlockchainEvents<Block> + HeaderBackend<Block> + Send + Sync + 'static,
 {
-    fn get_username(&self, account_id: AccountId) -> RpcResult<Option<String>> {
-        let api = self.client.runtime_api();
-        let at = self.client.info().best_hash;
+    /// Cache up to `size` lookups, see [`DEFAULT_CACHE_SIZE`]. Zero disables the cache.
+    pub fn with_cache_size(mut self, size: u32) -> Self {
+        self.cache = (size > 0).then(|| {
+            let cache = Arc::new(cache::UsernameCache::new(size));
+            cache::spawn_follower(&self.executor, self.client.clone(), cache.clone());
+            cache
+        });
+        self
+    }
+}
+
+impl<C, Block> UsernameStorageRpc<C, Block>
+where
+    Block: BlockT,
+    C: HeaderBackend<Block> + BlockBackend<Block>,
+{
+    /// Resolve the block to read from and make sure its state is still available.
+    fn state_at(&self, at: Option<Block::Hash>, finalized: Option<bool>) -> RpcResult<Block::Hash> {
+        let info = self.client.info();
+        let at = at.unwrap_or(if finalized.unwrap_or(false) {
+            info.finalized_hash
+        } else {
+            info.best_hash
+        });
 
-        api.get_username(at, account_id)
-            .map(|opt| opt.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
-            .map_err(|e| {
-                ErrorObject::owned(
-                    ErrorCode::ServerError(1).code(),
-                    "Unable to get username",
-                    Some(format!("{:?}", e)),
-                )
-            })
+        match self.client.block_status(at) {
+            Ok(BlockStatus::InChainWithState) => Ok(at),
+            Ok(BlockStatus::InChainPruned) => Err(Error::StatePruned(format!("{:?}", at)).into()),
+            Ok(_) => Err(Error::UnknownBlock(format!("{:?}", at)).into()),
+            Err(e) => Err(Error::RuntimeApi(format!("{:?}", e)).into()),
+        }
     }
+}
 
-    fn set_username(&self, account_id: AccountId, username: String) -> RpcResult<()> {
+impl<C, Block> UsernameStorageRpc<C, Block>
+where
+    Block: BlockT,
+    C: ProvideRuntimeApi<Block>,
+{
+    /// The usernames of `account_ids` in the default registry, through the cache.
+    fn default_usernames<AccountId>(
+        &self,
+        at: Block::Hash,
+        account_ids: Vec<AccountId>,
+    ) -> RpcResult<Vec<Option<Vec<u8>>>>
+    where
+        C::Api: UsernameStorageApi<Block, AccountId>,
+        AccountId: Codec + 'static,
+    {
+        self.cached(at, account_ids, |account_ids| {
+            let api = self.client.runtime_api();
+
+            // Runtimes older than version 2 of the API only support single lookups.
+            let version = api
+                .api_version::<dyn UsernameStorageApi<Block, AccountId>>(at)
+                .map_err(Error::from)?
+                .ok_or(Error::UnsupportedApi {
+                    required: 1,
+                    found: None,
+                })?;
+            let usernames = if version >= 2 {
+                api.get_usernames(at, account_ids).map_err(Error::from)?
+            } else {
+                account_ids
+                    .into_iter()
+                    .map(|account_id| api.get_username(at, account_id))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(Error::from)?
+            };
+            Ok(usernames)
+        })
+    }
+
+    /// The usernames of `account_ids` in `registry`.
+    fn registry_usernames<AccountId>(
+        &self,
+        at: Block::Hash,
+        registry: String,
+        account_ids: Vec<AccountId>,
+    ) -> RpcResult<Vec<Option<Vec<u8>>>>
+    where
+        C::Api: UsernameStorageApi<Block, AccountId>,
+        AccountId: Codec + 'static,
+    {
         let api = self.client.runtime_api();
-        let at = self.client.info().best_hash;
-
-        api.set_username(at, account_id, username.into_bytes())
-            .map_err(|e| {
-                ErrorObject::owned(
-                    ErrorCode::ServerError(2).code(),
-                    "Unable to set username",
-                    Some(format!("{:?}", e)),
-                )
-            })?
-            .map_err(|e| {
-                ErrorObject::owned(
-                    ErrorCode::ServerError(3).code(),
-                    "Failed to set username",
-                    Some(format!("{:?}", e)),
-                )
-            })
+        check_registry_api::<Block, AccountId>(&*api, at, REGISTRY_API_VERSION)?;
+        api.registry_usernames(at, registry.clone().into_bytes(), account_ids)
+            .map_err(Error::from)?
+            .ok_or_else(|| Error::UnknownRegistry(registry).into())
+    }
+}
+
+/// Make sure a request names at most `max` accounts.
+fn check_accounts(requested: usize, max: usize) -> Result<(), Error> {
+    if requested > max {
+        return Err(Error::TooManyAccounts { requested, max });
+    }
+    Ok(())
+}
+
+/// Make sure the runtime at `at` implements at least version `required` of `UsernameStorageApi`,
+/// to serve registries other than the default one.
+fn check_registry_api<Block, AccountId>(
+    api: &impl ApiExt<Block>,
+    at: Block::Hash,
+    required: u32,
+) -> RpcResult<()>
+where
+    Block: BlockT,
+    AccountId: Codec + 'static,
+{
+    match api
+        .api_version::<dyn UsernameStorageApi<Block, AccountId>>(at)
+        .map_err(Error::from)?
+    {
+        Some(version) if version >= required => Ok(()),
+        found => Err(Error::UnsupportedApi { required, found }.into()),
+    }
+}
+
+impl<C, Block, AccountId> UsernameStorageRpcApiServer<AccountId, Block::Hash>
+    for UsernameStorageRpc<C, Block>
+where
+    Block: BlockT,
+    C: Send
+        + Sync
+        + 'static
+        + ProvideRuntimeApi<Block>
+        + HeaderBackend<Block>
+        + BlockBackend<Block>
+        + BlockchainEvents<Block>,
+    C::Api: UsernameStorageApi<Block, AccountId>,
+    AccountId: Codec + Clone + serde::Serialize + Send + Sync + 'static,
+{
+    fn get_username(
+        &self,
+        account_id: AccountId,
+        at: Option<Block::Hash>,
+        finalized: Option<bool>,
+        registry: Option<String>,
+    ) -> RpcResult<Option<Username>> {
+        self.measure("usernameStorage_getUsername", || {
+            let at = self.state_at(at, finalized)?;
+
+            if let Some(registry) = registry {
+                let usernames = self.registry_usernames(at, registry, vec![account_id])?;
+                return Ok(usernames.into_iter().next().flatten().map(Username::from));
+            }
+
+            let usernames = self.cached(at, vec![account_id], |accounts| {
+                let api = self.client.runtime_api();
+                accounts
+                    .into_iter()
+                    .map(|account_id| api.get_username(at, account_id))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| Error::from(e).into())
+            })?;
+            Ok(usernames.into_iter().next().flatten().map(Username::from))
+        })
+    }
+
+    fn get_usernames(
+        &self,
+        account_ids: Vec<AccountId>,
+        at: Option<Block::Hash>,
+        finalized: Option<bool>,
+        registry: Option<String>,
+    ) -> RpcResult<Vec<Option<Username>>> {
+        self.measure("usernameStorage_getUsernames", || {
+            check_accounts(account_ids.len(), self.max_batch_size)?;
+
+            let at = self.state_at(at, finalized)?;
+
+            // Only the default registry is cached.
+            let usernames = match registry {
+                Some(registry) => self.registry_usernames(at, registry, account_ids)?,
+                None => self.default_usernames(at, account_ids)?,
+            };
+
+            Ok(usernames
+                .into_iter()
+                .map(|opt| opt.map(Username::from))
+                .collect())
+        })
+    }
+
+    fn set_username(
+        &self,
+        account_id: AccountId,
+        username: String,
+        at: Option<Block::Hash>,
+        finalized: Option<bool>,
+        registry: Option<String>,
+    ) -> RpcResult<()> {
+        self.measure("usernameStorage_setUsername", || {
+            let api = self.client.runtime_api();
+            let at = self.state_at(at, finalized)?;
+
+            let result = match registry {
+                Some(registry) => {
+                    check_registry_api::<Block, AccountId>(&*api, at, REGISTRY_API_VERSION)?;
+                    api.registry_set_username(
+                        at,
+                        registry.clone().into_bytes(),
+                        account_id,
+                        username.into_bytes(),
+                    )
+                    .map_err(Error::from)?
+                    .ok_or(Error::UnknownRegistry(registry))?
+                }
+                None => api
+                    .set_username(at, account_id, username.into_bytes())
+                    .map_err(Error::from)?,
+            };
+            result.map_err(|e| Error::from_dispatch_error(e).into())
+        })
+    }
+
+    fn registries(
+        &self,
+        at: Option<Block::Hash>,
+        finalized: Option<bool>,
+    ) -> RpcResult<Vec<String>> {
+        self.measure("usernameStorage_registries", || {
+            let api = self.client.runtime_api();
+            let at = self.state_at(at, finalized)?;
+
+            check_registry_api::<Block, AccountId>(&*api, at, REGISTRY_API_VERSION)?;
+            let registries = api.registries(at).map_err(Error::from)?;
+            Ok(registries
+                .into_iter()
+                .map(|name| String::from_utf8_lossy(&name).into_owned())
+                .collect())
+        })
+    }
+
+    fn subscribe_username(
+        &self,
+        pending: PendingSubscriptionSink,
+        accounts: Vec<AccountId>,
+        finalized: Option<bool>,
+        registry: Option<String>,
+    ) {
+        // The name of the pallet instance holding the watched registry.
+        let checked = self.measure("usernameStorage_subscribeUsername", || {
+            check_accounts(accounts.len(), self.max_subscription_accounts)?;
+
+            let Some(registry) = &registry else {
+                return Ok(subscription::DEFAULT_PALLET.to_vec());
+            };
+            let api = self.client.runtime_api();
+            let at = self.client.info().best_hash;
+            check_registry_api::<Block, AccountId>(&*api, at, REGISTRY_PALLET_API_VERSION)?;
+            api.registry_pallet(at, registry.clone().into_bytes())
+                .map_err(Error::from)?
+                .ok_or_else(|| Error::UnknownRegistry(registry.clone()).into())
+        });
+        let pallet = match checked {
+            Ok(pallet) => pallet,
+            Err(error) => {
+                subscription::spawn(&self.executor, async move {
+                    pending.reject(error).await;
+                });
+                return;
+            }
+        };
+
+        let client = self.client.clone();
+        if finalized.unwrap_or(false) {
+            subscription::spawn(
+                &self.executor,
+                subscription::finalized(client, pending, accounts, registry),
+            );
+        } else {
+            subscription::spawn(
+                &self.executor,
+                subscription::best(client, pending, accounts, pallet),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpsee::types::error::ErrorObjectOwned;
+    use serde_json::json;
+
+    fn round_trip(username: &Username) -> Username {
+        serde_json::from_value(serde_json::to_value(username).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_utf8_username_round_trip() {
+        let username = Username::from("zoë".as_bytes().to_vec());
+        assert_eq!(username, Username::Utf8("zoë".into()));
+        assert_eq!(serde_json::to_value(&username).unwrap(), json!("zoë"));
+        assert_eq!(round_trip(&username), username);
+        assert_eq!(username.into_bytes(), "zoë".as_bytes());
+
+        // A name that looks like hex is still a plain name
+        let username = Username::from(b"0xff".to_vec());
+        assert_eq!(serde_json::to_value(&username).unwrap(), json!("0xff"));
+        assert_eq!(round_trip(&username).into_bytes(), b"0xff");
+    }
+
+    #[test]
+    fn test_raw_username_round_trip() {
+        let bytes = vec![0x61, 0xff, 0x00];
+        let username = Username::from(bytes.clone());
+        assert_eq!(
+            serde_json::to_value(&username).unwrap(),
+            json!({ "raw": "0x61ff00" })
+        );
+        assert_eq!(round_trip(&username), username);
+        assert_eq!(username.into_bytes(), bytes);
+
+        // Clients may send any name raw
+        let username: Username = serde_json::from_value(json!({ "raw": "0x616c696365" })).unwrap();
+        assert_eq!(username.into_bytes(), b"alice");
+    }
+
+    fn too_many_accounts(requested: usize, max: usize) -> ErrorObjectOwned {
+        check_accounts(requested, max).unwrap_err().into()
+    }
+
+    fn data(error: &ErrorObjectOwned) -> serde_json::Value {
+        serde_json::from_str(error.data().unwrap().get()).unwrap()
+    }
+
+    #[test]
+    fn test_batch_size_limit() {
+        assert!(check_accounts(DEFAULT_MAX_BATCH_SIZE, DEFAULT_MAX_BATCH_SIZE).is_ok());
+
+        let rejected = too_many_accounts(DEFAULT_MAX_BATCH_SIZE + 1, DEFAULT_MAX_BATCH_SIZE);
+        assert_eq!(rejected.code(), error::TOO_MANY_ACCOUNTS_ERROR);
+        assert_eq!(rejected.message(), "Too many accounts");
+        assert_eq!(data(&rejected), json!({ "requested": 257, "max": 256 }));
+    }
+
+    #[test]
+    fn test_subscription_accounts_limit() {
+        assert!(check_accounts(
+            DEFAULT_MAX_SUBSCRIPTION_ACCOUNTS,
+            DEFAULT_MAX_SUBSCRIPTION_ACCOUNTS
+        )
+        .is_ok());
+
+        let rejected = too_many_accounts(
+            DEFAULT_MAX_SUBSCRIPTION_ACCOUNTS + 1,
+            DEFAULT_MAX_SUBSCRIPTION_ACCOUNTS,
+        );
+        assert_eq!(rejected.code(), error::TOO_MANY_ACCOUNTS_ERROR);
+        assert_eq!(data(&rejected), json!({ "requested": 129, "max": 128 }));
     }
 }
@@ -0,0 +1,84 @@
+//! Prometheus metrics of the username storage RPC.
+
+use std::time::Duration;
+use substrate_prometheus_endpoint::{
+    register, CounterVec, HistogramOpts, HistogramVec, Opts, PrometheusError, Registry, U64,
+};
+
+/// Call counts, latencies and errors of the `usernameStorage_*` methods, and cache efficiency.
+#[derive(Clone)]
+pub struct RpcMetrics {
+    calls: CounterVec<U64>,
+    errors: CounterVec<U64>,
+    latency: HistogramVec,
+    cache: CounterVec<U64>,
+}
+
+impl RpcMetrics {
+    /// Register the metrics in `registry`.
+    pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        Ok(Self {
+            calls: register(
+                CounterVec::new(
+                    Opts::new(
+                        "username_rpc_calls_total",
+                        "Number of username storage RPC calls",
+                    ),
+                    &["method"],
+                )?,
+                registry,
+            )?,
+            errors: register(
+                CounterVec::new(
+                    Opts::new(
+                        "username_rpc_errors_total",
+                        "Number of failed username storage RPC calls, by error code",
+                    ),
+                    &["method", "code"],
+                )?,
+                registry,
+            )?,
+            latency: register(
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "username_rpc_call_duration_seconds",
+                        "Time spent serving username storage RPC calls",
+                    ),
+                    &["method"],
+                )?,
+                registry,
+            )?,
+            cache: register(
+                CounterVec::new(
+                    Opts::new(
+                        "username_rpc_cache_lookups_total",
+                        "Username lookups served by the RPC cache, by result",
+                    ),
+                    &["result"],
+                )?,
+                registry,
+            )?,
+        })
+    }
+
+    /// Record a call of `method` that took `elapsed` and failed with `error`, if any.
+    pub(crate) fn observe(&self, method: &str, elapsed: Duration, error: Option<i32>) {
+        self.calls.with_label_values(&[method]).inc();
+        self.latency
+            .with_label_values(&[method])
+            .observe(elapsed.as_secs_f64());
+        if let Some(code) = error {
+            self.errors
+                .with_label_values(&[method, &code.to_string()])
+                .inc();
+        }
+    }
+
+    /// Record `hits` lookups served by the cache and `misses` that went to the runtime.
+    pub(crate) fn observe_cache(&self, hits: usize, misses: usize) {
+        self.cache.with_label_values(&["hit"]).inc_by(hits as u64);
+        self.cache
+            .with_label_values(&["miss"])
+            .inc_by(misses as u64);
+    }
+}
@@ -0,0 +1,355 @@
+//! OpenRPC description of the username storage RPC, served by `rpc.discover`.
+//!
+//! The document is built from the same error table as the RPC itself, so codes and messages
+//! cannot drift apart. Method names and parameters mirror [`crate::UsernameStorageRpcApiServer`]
+//! and the node's `usernameStorage_events`, which the node's RPC tests check them against.
+
+use crate::{
+    error::{
+        DISPATCH_ERROR, INDEXER_ERROR, RUNTIME_API_ERROR, STATE_PRUNED_ERROR, SUBSCRIPTION_ERROR,
+        TOO_MANY_ACCOUNTS_ERROR, UNKNOWN_BLOCK_ERROR, UNKNOWN_REGISTRY_ERROR,
+        UNSUPPORTED_API_ERROR,
+    },
+    Error, PalletError,
+};
+use jsonrpsee::{core::RpcResult, proc_macros::rpc};
+use serde_json::{json, Value};
+
+/// OpenRPC specification version the document follows.
+const OPENRPC_VERSION: &str = "1.2.6";
+
+/// Service discovery for the username storage RPC.
+#[rpc(server)]
+pub trait DiscoverApi {
+    /// The OpenRPC document describing every `usernameStorage_*` method.
+    #[method(name = "rpc.discover")]
+    fn discover(&self) -> RpcResult<Value>;
+}
+
+/// Serves the OpenRPC document.
+pub struct Discover {
+    document: Value,
+}
+
+impl Discover {
+    pub fn new() -> Self {
+        Self {
+            document: document(),
+        }
+    }
+}
+
+impl Default for Discover {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiscoverApiServer for Discover {
+    fn discover(&self) -> RpcResult<Value> {
+        Ok(self.document.clone())
+    }
+}
+
+/// Every error of the RPC, with one representative per code.
+fn errors() -> Vec<Error> {
+    let mut errors = vec![
+        Error::RuntimeApi(String::new()),
+        Error::UnsupportedApi {
+            required: 0,
+            found: Some(0),
+        },
+        Error::UnknownBlock(String::new()),
+        Error::StatePruned(String::new()),
+        Error::TooManyAccounts {
+            requested: 0,
+            max: 0,
+        },
+        Error::Subscription(String::new()),
+        Error::Dispatch(String::new()),
+        Error::Indexer(String::new()),
+        Error::UnknownRegistry(String::new()),
+    ];
+    errors.extend(PalletError::ALL.into_iter().map(Error::Pallet));
+    errors
+}
+
+fn error_ref(code: i32) -> Value {
+    json!({ "$ref": format!("#/components/errors/{}", code) })
+}
+
+fn block_params() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "at",
+            "description": "Block to read from, takes precedence over `finalized`",
+            "required": false,
+            "schema": { "oneOf": [{ "$ref": "#/components/schemas/BlockHash" }, { "type": "null" }] }
+        }),
+        json!({
+            "name": "finalized",
+            "description": "Read the finalized block instead of the best block",
+            "required": false,
+            "schema": { "type": ["boolean", "null"] }
+        }),
+    ]
+}
+
+fn registry_param() -> Value {
+    json!({
+        "name": "registry",
+        "description": "Username registry, as listed by `usernameStorage_registries`, the default \
+            registry when not given",
+        "required": false,
+        "schema": { "type": ["string", "null"] }
+    })
+}
+
+fn registry_errors() -> Vec<Value> {
+    vec![
+        error_ref(UNSUPPORTED_API_ERROR),
+        error_ref(UNKNOWN_REGISTRY_ERROR),
+    ]
+}
+
+fn block_errors() -> Vec<Value> {
+    [RUNTIME_API_ERROR, UNKNOWN_BLOCK_ERROR, STATE_PRUNED_ERROR]
+        .into_iter()
+        .map(error_ref)
+        .collect()
+}
+
+fn account_param(name: &str) -> Value {
+    json!({
+        "name": name,
+        "required": true,
+        "schema": { "$ref": "#/components/schemas/AccountId" }
+    })
+}
+
+fn accounts_param(name: &str) -> Value {
+    json!({
+        "name": name,
+        "required": true,
+        "schema": { "type": "array", "items": { "$ref": "#/components/schemas/AccountId" } }
+    })
+}
+
+fn optional_username() -> Value {
+    json!({ "oneOf": [{ "$ref": "#/components/schemas/Username" }, { "type": "null" }] })
+}
+
+fn methods() -> Value {
+    let mut get_username_params = vec![account_param("account_id")];
+    get_username_params.extend(block_params());
+    get_username_params.push(registry_param());
+    let mut get_username_errors = block_errors();
+    get_username_errors.extend(registry_errors());
+
+    let mut get_usernames_params = vec![accounts_param("account_ids")];
+    get_usernames_params.extend(block_params());
+    get_usernames_params.push(registry_param());
+    let mut get_usernames_errors = block_errors();
+    get_usernames_errors.extend([
+        error_ref(UNSUPPORTED_API_ERROR),
+        error_ref(TOO_MANY_ACCOUNTS_ERROR),
+        error_ref(UNKNOWN_REGISTRY_ERROR),
+    ]);
+
+    let mut set_username_params = vec![
+        account_param("account_id"),
+        json!({ "name": "username", "required": true, "schema": { "type": "string" } }),
+    ];
+    set_username_params.extend(block_params());
+    set_username_params.push(registry_param());
+    let mut set_username_errors = block_errors();
+    set_username_errors.extend(registry_errors());
+    set_username_errors.push(error_ref(DISPATCH_ERROR));
+    set_username_errors.extend(PalletError::ALL.iter().map(|e| error_ref(e.code())));
+
+    json!([
+        {
+            "name": "usernameStorage_getUsername",
+            "summary": "The username of an account",
+            "params": get_username_params,
+            "result": { "name": "username", "schema": optional_username() },
+            "errors": get_username_errors,
+        },
+        {
+            "name": "usernameStorage_getUsernames",
+            "summary": "The usernames of several accounts, in the same order",
+            "params": get_usernames_params,
+            "result": {
+                "name": "usernames",
+                "schema": { "type": "array", "items": optional_username() }
+            },
+            "errors": get_usernames_errors,
+        },
+        {
+            "name": "usernameStorage_setUsername",
+            "summary": "Dry-run `set_username` for an account against the given block",
+            "params": set_username_params,
+            "result": { "name": "result", "schema": { "type": "null" } },
+            "errors": set_username_errors,
+        },
+        {
+            "name": "usernameStorage_registries",
+            "summary": "Names of the username registries, the default one first",
+            "params": block_params(),
+            "result": {
+                "name": "registries",
+                "schema": { "type": "array", "items": { "type": "string" } }
+            },
+            "errors": [
+                error_ref(RUNTIME_API_ERROR),
+                error_ref(UNKNOWN_BLOCK_ERROR),
+                error_ref(STATE_PRUNED_ERROR),
+                error_ref(UNSUPPORTED_API_ERROR),
+            ],
+        },
+        {
+            "name": "usernameStorage_subscribeUsername",
+            "summary": "Watch the usernames of accounts",
+            "description": "Returns a subscription id. Every change is pushed as a \
+                `usernameStorage_username` notification carrying a `UsernameChange`. Changes are \
+                reported on new best blocks, or only on finalized blocks when `finalized` is true.",
+            "params": [
+                accounts_param("accounts"),
+                {
+                    "name": "finalized",
+                    "required": false,
+                    "schema": { "type": ["boolean", "null"] }
+                },
+                registry_param(),
+            ],
+            "result": { "name": "subscription", "schema": { "type": "string" } },
+            "errors": [
+                error_ref(RUNTIME_API_ERROR),
+                error_ref(TOO_MANY_ACCOUNTS_ERROR),
+                error_ref(SUBSCRIPTION_ERROR),
+                error_ref(UNSUPPORTED_API_ERROR),
+                error_ref(UNKNOWN_REGISTRY_ERROR),
+            ],
+            "x-notification": {
+                "method": "usernameStorage_username",
+                "schema": { "$ref": "#/components/schemas/UsernameChange" }
+            },
+        },
+        {
+            "name": "usernameStorage_unsubscribeUsername",
+            "summary": "Stop a username subscription",
+            "params": [{ "name": "subscription", "required": true, "schema": { "type": "string" } }],
+            "result": { "name": "unsubscribed", "schema": { "type": "boolean" } },
+        },
+        {
+            "name": "usernameStorage_events",
+            "summary": "History of the username events involving an account",
+            "description": "Only served by nodes started with `--username-indexer`. Events are \
+                returned in chain order, from finalized blocks and from the current best chain.",
+            "params": [
+                account_param("account"),
+                {
+                    "name": "from_block",
+                    "description": "First block to include, the genesis block by default",
+                    "required": false,
+                    "schema": { "type": ["integer", "null"] }
+                },
+                {
+                    "name": "to_block",
+                    "description": "Last block to include, the best block by default",
+                    "required": false,
+                    "schema": { "type": ["integer", "null"] }
+                }
+            ],
+            "result": {
+                "name": "events",
+                "schema": { "type": "array", "items": { "$ref": "#/components/schemas/UsernameEvent" } }
+            },
+            "errors": [error_ref(INDEXER_ERROR)],
+        },
+    ])
+}
+
+/// The OpenRPC document of the username storage RPC.
+pub fn document() -> Value {
+    let errors: serde_json::Map<String, Value> = errors()
+        .into_iter()
+        .map(|error| {
+            (
+                error.code().to_string(),
+                json!({ "code": error.code(), "message": error.message() }),
+            )
+        })
+        .collect();
+
+    json!({
+        "openrpc": OPENRPC_VERSION,
+        "info": {
+            "title": "Username storage RPC",
+            "description": "Custom methods of the username storage chain. Error codes are stable, \
+                see the `data` field of each error for details.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "methods": methods(),
+        "components": {
+            "schemas": {
+                "AccountId": {
+                    "type": "string",
+                    "description": "SS58 encoded account",
+                },
+                "BlockHash": {
+                    "type": "string",
+                    "pattern": "^0x[0-9a-fA-F]{64}$",
+                },
+                "Username": {
+                    "description": "A UTF-8 username, or the hex encoded bytes of a name that is \
+                        not valid UTF-8",
+                    "oneOf": [
+                        { "type": "string" },
+                        {
+                            "type": "object",
+                            "required": ["raw"],
+                            "properties": { "raw": { "type": "string" } },
+                        },
+                    ],
+                },
+                "UsernameChange": {
+                    "type": "object",
+                    "required": ["account", "username", "blockHash"],
+                    "properties": {
+                        "account": { "$ref": "#/components/schemas/AccountId" },
+                        "username": optional_username(),
+                        "blockHash": { "$ref": "#/components/schemas/BlockHash" },
+                    },
+                },
+                "UsernameEvent": {
+                    "type": "object",
+                    "required": ["blockNumber", "blockHash", "eventIndex", "registry", "event"],
+                    "properties": {
+                        "blockNumber": { "type": "integer" },
+                        "blockHash": { "$ref": "#/components/schemas/BlockHash" },
+                        "eventIndex": { "type": "integer" },
+                        "registry": {
+                            "type": "string",
+                            "description": "Name of the username registry of the event",
+                        },
+                        "event": {
+                            "type": "string",
+                            "description": "Name of the `pallet_username_storage` event",
+                        },
+                        "username": optional_username(),
+                        "counterparty": {
+                            "description": "The other account of a retrieval, transfer or sale",
+                            "oneOf": [{ "$ref": "#/components/schemas/AccountId" }, { "type": "null" }],
+                        },
+                        "price": {
+                            "description": "Price of a listing or sale, as a decimal string",
+                            "type": ["string", "null"],
+                        },
+                    },
+                },
+            },
+            "errors": errors,
+        },
+    })
+}
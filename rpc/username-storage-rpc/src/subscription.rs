@@ -0,0 +1,208 @@
+//! Username change subscriptions.
+
+use crate::{Error, Username};
+use codec::{Codec, Decode, Encode};
+use futures::{
+    future::{self, Either},
+    stream, FutureExt, Stream, StreamExt,
+};
+use jsonrpsee::{PendingSubscriptionSink, SubscriptionMessage};
+use pallet_username_storage::UsernameStorageApi;
+use sc_client_api::BlockchainEvents;
+use sc_rpc::SubscriptionTaskExecutor;
+use serde::{Deserialize, Serialize};
+use sp_api::{ApiError, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+    hashing::{blake2_128, twox_128},
+    storage::StorageKey,
+};
+use sp_runtime::traits::Block as BlockT;
+use std::{future::Future, sync::Arc};
+
+/// Name of the pallet instance holding the default username registry in the runtime.
+pub(crate) const DEFAULT_PALLET: &[u8] = b"UsernameStorage";
+/// Name of the storage map holding usernames by account.
+const USERNAMES_PREFIX: &[u8] = b"Usernames";
+
+/// Notification pushed to subscribers when the username of a watched account changes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsernameChange<AccountId, Hash> {
+    /// The account whose username changed
+    pub account: AccountId,
+    /// The new username, `None` when it was removed
+    pub username: Option<Username>,
+    /// The block in which the change happened
+    pub block_hash: Hash,
+}
+
+/// Storage key prefix of the `Usernames` map of the pallet instance named `pallet`.
+pub(crate) fn usernames_prefix(pallet: &[u8]) -> Vec<u8> {
+    let mut key = twox_128(pallet).to_vec();
+    key.extend(twox_128(USERNAMES_PREFIX));
+    key
+}
+
+/// Storage key of the `Usernames` entry for `account` in the pallet instance named `pallet`.
+fn username_key<AccountId: Encode>(pallet: &[u8], account: &AccountId) -> StorageKey {
+    let encoded = account.encode();
+    let mut key = usernames_prefix(pallet);
+    key.extend(blake2_128(&encoded));
+    key.extend(encoded);
+    StorageKey(key)
+}
+
+pub(crate) fn spawn(
+    executor: &SubscriptionTaskExecutor,
+    fut: impl Future<Output = ()> + Send + 'static,
+) {
+    executor.spawn("username-storage-subscription", Some("rpc"), fut.boxed());
+}
+
+/// Accept the subscription and forward every item of `stream` until either side closes.
+async fn pipe<S, T>(pending: PendingSubscriptionSink, mut stream: S)
+where
+    S: Stream<Item = T> + Unpin,
+    T: Serialize,
+{
+    let Ok(sink) = pending.accept().await else {
+        return;
+    };
+
+    let closed = sink.closed();
+    futures::pin_mut!(closed);
+
+    loop {
+        match future::select(closed.as_mut(), stream.next()).await {
+            Either::Right((Some(item), _)) => {
+                let Ok(message) = SubscriptionMessage::from_json(&item) else {
+                    break;
+                };
+                if sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+            Either::Right((None, _)) | Either::Left(_) => break,
+        }
+    }
+}
+
+/// Report changes as soon as they are imported in a new best block.
+///
+/// `pallet` is the name of the pallet instance holding the watched registry.
+pub(crate) async fn best<C, Block, AccountId>(
+    client: Arc<C>,
+    pending: PendingSubscriptionSink,
+    accounts: Vec<AccountId>,
+    pallet: Vec<u8>,
+) where
+    Block: BlockT,
+    C: BlockchainEvents<Block>,
+    AccountId: Encode + Clone + Serialize,
+{
+    let keys: Vec<StorageKey> = accounts
+        .iter()
+        .map(|account| username_key(&pallet, account))
+        .collect();
+    let notifications = match client.storage_changes_notification_stream(Some(&keys), None) {
+        Ok(notifications) => notifications,
+        Err(e) => {
+            pending
+                .reject(Error::Subscription(format!("{:?}", e)))
+                .await;
+            return;
+        }
+    };
+
+    let changes = notifications.flat_map(move |notification| {
+        let changes = notification
+            .changes
+            .iter()
+            .filter(|(child, _, _)| child.is_none())
+            .filter_map(|(_, key, value)| {
+                let index = keys.iter().position(|k| k == key)?;
+                Some(UsernameChange {
+                    account: accounts[index].clone(),
+                    username: value
+                        .and_then(|data| Vec::<u8>::decode(&mut &data.0[..]).ok())
+                        .map(Username::from),
+                    block_hash: notification.block,
+                })
+            })
+            .collect::<Vec<_>>();
+        stream::iter(changes)
+    });
+
+    pipe(pending, changes).await
+}
+
+/// The usernames of `accounts` at `at`, in `registry` or the default registry.
+fn usernames_at<C, Block, AccountId>(
+    client: &C,
+    accounts: &[AccountId],
+    at: Block::Hash,
+    registry: Option<&str>,
+) -> Result<Vec<Option<Vec<u8>>>, Error>
+where
+    Block: BlockT,
+    C: ProvideRuntimeApi<Block>,
+    C::Api: UsernameStorageApi<Block, AccountId>,
+    AccountId: Codec + Clone,
+{
+    let api = client.runtime_api();
+    match registry {
+        Some(registry) => api
+            .registry_usernames(at, registry.as_bytes().to_vec(), accounts.to_vec())?
+            .ok_or_else(|| Error::UnknownRegistry(registry.into())),
+        None => Ok(accounts
+            .iter()
+            .map(|account| api.get_username(at, account.clone()))
+            .collect::<Result<_, ApiError>>()?),
+    }
+}
+
+/// Report changes once the block they happened in is finalized.
+pub(crate) async fn finalized<C, Block, AccountId>(
+    client: Arc<C>,
+    pending: PendingSubscriptionSink,
+    accounts: Vec<AccountId>,
+    registry: Option<String>,
+) where
+    Block: BlockT,
+    C: BlockchainEvents<Block> + ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+    C::Api: UsernameStorageApi<Block, AccountId>,
+    AccountId: Codec + Clone + Serialize,
+{
+    let registry = registry.as_deref();
+    let mut last = match usernames_at(&*client, &accounts, client.info().finalized_hash, registry) {
+        Ok(usernames) => usernames,
+        Err(e) => {
+            pending.reject(e).await;
+            return;
+        }
+    };
+
+    let finality = client.finality_notification_stream();
+    let changes = finality.flat_map(move |notification| {
+        // A failed read is skipped, the change is reported with the next finalized block.
+        let Ok(current) = usernames_at(&*client, &accounts, notification.hash, registry) else {
+            return stream::iter(Vec::new());
+        };
+
+        let changes = accounts
+            .iter()
+            .zip(last.iter().zip(current.iter()))
+            .filter(|(_, (old, new))| old != new)
+            .map(|(account, (_, new))| UsernameChange {
+                account: account.clone(),
+                username: new.clone().map(Username::from),
+                block_hash: notification.hash,
+            })
+            .collect::<Vec<_>>();
+        last = current;
+        stream::iter(changes)
+    });
+
+    pipe(pending, changes).await
+}
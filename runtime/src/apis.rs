@@ -27,6 +27,7 @@
 use alloc::vec::Vec;
 use frame_support::{
     genesis_builder_helper::{build_state, get_preset},
+    traits::PalletInfoAccess,
     weights::Weight,
 };
 use pallet_grandpa::AuthorityId as GrandpaId;
@@ -40,12 +41,38 @@ use sp_runtime::{
 };
 use sp_version::RuntimeVersion;
 
-use crate::UsernameStorage;
+use crate::{BotUsernames, OrganizationUsernames, UsernameStorage};
+use pallet_username_storage::UsernameProvider;
+
+/// Names of the username registries served by `UsernameStorageApi`, the default one first.
+const USERNAME_REGISTRIES: [&[u8]; 3] = [b"users", b"bots", b"organizations"];
+
+/// Evaluate `$body` with `$registry` standing for the pallet instance of the username registry
+/// named `$name`, `None` when there is no such registry.
+macro_rules! with_registry {
+    ($name:expr, $registry:ident => $body:expr) => {
+        match &$name[..] {
+            b"users" => {
+                type $registry = UsernameStorage;
+                Some($body)
+            }
+            b"bots" => {
+                type $registry = BotUsernames;
+                Some($body)
+            }
+            b"organizations" => {
+                type $registry = OrganizationUsernames;
+                Some($body)
+            }
+            _ => None,
+        }
+    };
+}
 
 // Local module imports
 use super::{
-    AccountId, Aura, Balance, Block, Executive, Grandpa, InherentDataExt, Nonce, Runtime,
-    RuntimeCall, RuntimeGenesisConfig, SessionKeys, System, TransactionPayment, VERSION,
+    AccountId, Aura, Balance, Block, BlockNumber, Executive, Grandpa, InherentDataExt, Nonce,
+    Runtime, RuntimeCall, RuntimeGenesisConfig, SessionKeys, System, TransactionPayment, VERSION,
 };
 
 impl_runtime_apis! {
@@ -304,6 +331,7 @@ impl_runtime_apis! {
         }
     }
 
+    #[api_version(7)]
     impl pallet_username_storage::UsernameStorageApi<Block, AccountId> for Runtime {
         fn get_username(account_id: AccountId) -> Option<Vec<u8>> {
             UsernameStorage::usernames(account_id).map(|v| v.into_inner().to_vec())
@@ -315,5 +343,112 @@ impl_runtime_apis! {
                 username,
             )
         }
+
+        fn get_usernames(account_ids: Vec<AccountId>) -> Vec<Option<Vec<u8>>> {
+            account_ids
+                .into_iter()
+                .map(|account_id| UsernameStorage::usernames(account_id).map(|v| v.into_inner()))
+                .collect()
+        }
+
+        fn get_account(username: Vec<u8>) -> Option<AccountId> {
+            UsernameStorage::account_of(&username)
+        }
+
+        fn usernames_page(start_after: Option<AccountId>, limit: u32) -> Vec<(AccountId, Vec<u8>)> {
+            UsernameStorage::usernames_page(start_after, limit)
+        }
+
+        fn username_count() -> u32 {
+            pallet_username_storage::UsernameCount::<Runtime>::get()
+        }
+
+        fn pending_username_change(account_id: AccountId) -> Option<(Vec<u8>, BlockNumber)> {
+            UsernameStorage::pending_change(&account_id)
+        }
+
+        fn pending_username_changes() -> Vec<(AccountId, Vec<u8>, BlockNumber)> {
+            UsernameStorage::pending_changes()
+        }
+
+        fn registries() -> Vec<Vec<u8>> {
+            USERNAME_REGISTRIES.iter().map(|name| name.to_vec()).collect()
+        }
+
+        fn registry_usernames(
+            registry: Vec<u8>,
+            account_ids: Vec<AccountId>,
+        ) -> Option<Vec<Option<Vec<u8>>>> {
+            with_registry!(registry, Registry => account_ids
+                .iter()
+                .map(Registry::username_of)
+                .collect())
+        }
+
+        fn registry_account(registry: Vec<u8>, username: Vec<u8>) -> Option<Option<AccountId>> {
+            with_registry!(registry, Registry => Registry::account_of(&username))
+        }
+
+        fn registry_set_username(
+            registry: Vec<u8>,
+            account_id: AccountId,
+            username: Vec<u8>,
+        ) -> Option<Result<(), sp_runtime::DispatchError>> {
+            with_registry!(registry, Registry => Registry::set_username(
+                frame_system::RawOrigin::Signed(account_id).into(),
+                username,
+            ))
+        }
+
+        fn registry_pallet(registry: Vec<u8>) -> Option<Vec<u8>> {
+            with_registry!(registry, Registry => Registry::name().as_bytes().to_vec())
+        }
+
+        fn registry_pending_username_change(
+            registry: Vec<u8>,
+            account_id: AccountId,
+        ) -> Option<Option<(Vec<u8>, BlockNumber)>> {
+            with_registry!(registry, Registry => Registry::pending_change(&account_id))
+        }
+
+        fn registry_pending_username_changes(
+            registry: Vec<u8>,
+        ) -> Option<Vec<(AccountId, Vec<u8>, BlockNumber)>> {
+            with_registry!(registry, Registry => Registry::pending_changes())
+        }
+
+        fn registry_usernames_page(
+            registry: Vec<u8>,
+            start_after: Option<AccountId>,
+            limit: u32,
+        ) -> Option<Vec<(AccountId, Vec<u8>)>> {
+            with_registry!(registry, Registry => Registry::usernames_page(start_after, limit))
+        }
+
+        fn registry_username_count(registry: Vec<u8>) -> Option<u32> {
+            with_registry!(registry, Registry => Registry::username_count())
+        }
+    }
+
+    #[api_version(2)]
+    impl pallet_username_storage::UsernameMarketApi<Block, AccountId, Balance> for Runtime {
+        fn listings() -> Vec<(Vec<u8>, AccountId, Balance)> {
+            UsernameStorage::listings()
+        }
+
+        fn listing(username: Vec<u8>) -> Option<(AccountId, Balance)> {
+            UsernameStorage::listing(&username)
+        }
+
+        fn registry_listings(registry: Vec<u8>) -> Option<Vec<(Vec<u8>, AccountId, Balance)>> {
+            with_registry!(registry, Registry => Registry::listings())
+        }
+
+        fn registry_listing(
+            registry: Vec<u8>,
+            username: Vec<u8>,
+        ) -> Option<Option<(AccountId, Balance)>> {
+            with_registry!(registry, Registry => Registry::listing(&username))
+        }
     }
 }
@@ -26,23 +26,33 @@
 // Substrate and Polkadot dependencies
 use frame_support::{
     derive_impl, parameter_types,
-    traits::{ConstBool, ConstU128, ConstU32, ConstU64, ConstU8, VariantCountOf},
+    traits::{
+        ConstBool, ConstU128, ConstU32, ConstU64, ConstU8, EitherOfDiverse, EnsureNever,
+        EqualPrivilegeOnly, VariantCountOf,
+    },
     weights::{
         constants::{RocksDbWeight, WEIGHT_REF_TIME_PER_SECOND},
         IdentityFee, Weight,
     },
 };
-use frame_system::limits::{BlockLength, BlockWeights};
+use frame_system::{
+    limits::{BlockLength, BlockWeights},
+    EnsureRoot,
+};
 use pallet_transaction_payment::{ConstFeeMultiplier, FungibleAdapter, Multiplier};
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
-use sp_runtime::{traits::One, Perbill};
+use sp_runtime::{
+    traits::{ConvertInto, One, OpaqueKeys},
+    Perbill,
+};
 use sp_version::RuntimeVersion;
 
 // Local module imports
 use super::{
-    AccountId, Aura, Balance, Balances, Block, BlockNumber, Hash, Nonce, PalletInfo, Runtime,
-    RuntimeCall, RuntimeEvent, RuntimeFreezeReason, RuntimeHoldReason, RuntimeOrigin, RuntimeTask,
-    System, EXISTENTIAL_DEPOSIT, SLOT_DURATION, VERSION,
+    AccountId, Aura, Balance, Balances, Block, BlockNumber, Council, Hash, Nonce, OriginCaller,
+    PalletInfo, Runtime, RuntimeCall, RuntimeEvent, RuntimeFreezeReason, RuntimeHoldReason,
+    RuntimeOrigin, RuntimeTask, Scheduler, Session, SessionKeys, System, ValidatorSet, DAYS,
+    EXISTENTIAL_DEPOSIT, MINUTES, SLOT_DURATION, VERSION,
 };
 
 const NORMAL_DISPATCH_RATIO: Perbill = Perbill::from_percent(75);
@@ -73,6 +83,9 @@ impl frame_system::Config for Runtime {
     type BlockLength = RuntimeBlockLength;
     /// The identifier used to distinguish between accounts.
     type AccountId = AccountId;
+    /// The lookup mechanism to get account ID from whatever is passed in dispatchers.
+    /// `MultiAddress::Raw` is resolved as a username.
+    type Lookup = pallet_username_storage::UsernameLookup<Runtime>;
     /// The type for storing how many extrinsics an account has signed.
     type Nonce = Nonce;
     /// The type for hashing blocks and tries.
@@ -151,6 +164,10 @@ impl pallet_transaction_payment::Config for Runtime {
     type WeightInfo = pallet_transaction_payment::weights::SubstrateWeight<Runtime>;
 }
 
+impl pallet_skip_feeless_payment::Config for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+}
+
 impl pallet_sudo::Config for Runtime {
     type RuntimeEvent = RuntimeEvent;
     type RuntimeCall = RuntimeCall;
@@ -168,4 +185,198 @@ impl pallet_username_storage::Config for Runtime {
 
     /// Maximum length of username
     type MaxUsernameLength = ConstU32<32>;
+    /// Any name is a valid user name
+    type UsernameRules = ();
+    /// No pallet in this runtime reacts to username changes yet
+    type OnUsernameChanged = ();
+    /// Fee-less first registrations allowed per block
+    type MaxSponsoredPerBlock = ConstU32<10>;
+    /// Usernames are bought with the native currency
+    type Currency = Balances;
+    /// Usernames are moderated by a majority of the council
+    type AdminOrigin = CouncilMajority;
+    type PalletsOrigin = OriginCaller;
+    /// Scheduled username changes are applied by the scheduler
+    type Scheduler = Scheduler;
+    /// Usernames are reserved for at most 30 days ahead of a change
+    type MaxChangeDelay = ConstU32<{ 30 * DAYS }>;
+}
+
+/// The registry of bot usernames.
+pub type BotRegistry = frame_support::instances::Instance1;
+
+/// The registry of organization usernames.
+pub type OrganizationRegistry = frame_support::instances::Instance2;
+
+/// Bot usernames end with `bot`, so they cannot pass for people.
+pub struct BotUsernameRules;
+
+impl pallet_username_storage::UsernameRules for BotUsernameRules {
+    fn is_allowed(username: &[u8]) -> bool {
+        username
+            .len()
+            .checked_sub(3)
+            .is_some_and(|start| username[start..].eq_ignore_ascii_case(b"bot"))
+    }
+}
+
+/// Organization usernames are made of lowercase ASCII letters, digits and dashes.
+pub struct OrganizationUsernameRules;
+
+impl pallet_username_storage::UsernameRules for OrganizationUsernameRules {
+    fn is_allowed(username: &[u8]) -> bool {
+        username
+            .iter()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || *c == b'-')
+    }
+}
+
+impl pallet_username_storage::Config<BotRegistry> for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+
+    type MaxUsernameLength = ConstU32<32>;
+    type UsernameRules = BotUsernameRules;
+    type OnUsernameChanged = ();
+    /// Bots pay for their registrations
+    type MaxSponsoredPerBlock = ConstU32<0>;
+    type Currency = Balances;
+    type AdminOrigin = CouncilMajority;
+    type PalletsOrigin = OriginCaller;
+    type Scheduler = Scheduler;
+    type MaxChangeDelay = ConstU32<{ 30 * DAYS }>;
+}
+
+impl pallet_username_storage::Config<OrganizationRegistry> for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+
+    /// Room for full legal names
+    type MaxUsernameLength = ConstU32<64>;
+    type UsernameRules = OrganizationUsernameRules;
+    type OnUsernameChanged = ();
+    /// Organizations pay for their registrations
+    type MaxSponsoredPerBlock = ConstU32<0>;
+    type Currency = Balances;
+    type AdminOrigin = CouncilMajority;
+    type PalletsOrigin = OriginCaller;
+    type Scheduler = Scheduler;
+    type MaxChangeDelay = ConstU32<{ 30 * DAYS }>;
+}
+
+parameter_types! {
+    /// Validator set changes take effect at session boundaries.
+    pub const SessionPeriod: BlockNumber = 10 * MINUTES;
+    pub const SessionOffset: BlockNumber = 0;
+}
+
+impl pallet_session::Config for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+    /// Validators are identified by their account
+    type ValidatorId = AccountId;
+    type ValidatorIdOf = ConvertInto;
+    type ShouldEndSession = pallet_session::PeriodicSessions<SessionPeriod, SessionOffset>;
+    type NextSessionRotation = pallet_session::PeriodicSessions<SessionPeriod, SessionOffset>;
+    /// The validators of each session come from the validator set pallet
+    type SessionManager = ValidatorSet;
+    /// Aura and GRANDPA authorities are updated from the session keys
+    type SessionHandler = <SessionKeys as OpaqueKeys>::KeyTypeIdProviders;
+    type Keys = SessionKeys;
+    type DisablingStrategy = ();
+    type WeightInfo = pallet_session::weights::SubstrateWeight<Runtime>;
+}
+
+impl pallet_validator_set::Config for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+
+    /// Validators are added and removed by root, through sudo
+    type AddRemoveOrigin = EnsureRoot<AccountId>;
+    /// Validators must register their session keys first
+    type ValidatorRegistration = Session;
+    /// Aura needs at least one block author
+    type MinValidators = ConstU32<1>;
+    /// Same bound as the Aura and GRANDPA authorities
+    type MaxValidators = ConstU32<32>;
+}
+
+impl pallet_node_allowlist::Config for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+
+    /// Nodes are allowed and removed by root, through sudo
+    type AdminOrigin = EnsureRoot<AccountId>;
+    /// Maximum number of nodes of a permissioned network
+    type MaxNodes = ConstU32<64>;
+    /// Ed25519 peer ids are 38 bytes, leave room for other key types
+    type MaxPeerIdLength = ConstU32<128>;
+}
+
+/// The council moderating usernames.
+pub type CouncilCollective = pallet_collective::Instance1;
+
+/// More than half of the council.
+pub type CouncilMajority =
+    pallet_collective::EnsureProportionMoreThan<AccountId, CouncilCollective, 1, 2>;
+
+/// At least two thirds of the council.
+pub type CouncilTwoThirds =
+    pallet_collective::EnsureProportionAtLeast<AccountId, CouncilCollective, 2, 3>;
+
+/// Root or at least two thirds of the council.
+pub type RootOrCouncilTwoThirds = EitherOfDiverse<EnsureRoot<AccountId>, CouncilTwoThirds>;
+
+parameter_types! {
+    pub const CouncilMotionDuration: BlockNumber = 3 * DAYS;
+    pub const CouncilMaxProposals: u32 = 100;
+    pub const CouncilMaxMembers: u32 = 100;
+    pub MaxProposalWeight: Weight = Perbill::from_percent(50) * RuntimeBlockWeights::get().max_block;
+}
+
+impl pallet_collective::Config<CouncilCollective> for Runtime {
+    type RuntimeOrigin = RuntimeOrigin;
+    type Proposal = RuntimeCall;
+    type RuntimeEvent = RuntimeEvent;
+    type MotionDuration = CouncilMotionDuration;
+    type MaxProposals = CouncilMaxProposals;
+    type MaxMembers = CouncilMaxMembers;
+    type DefaultVote = pallet_collective::PrimeDefaultVote;
+    type WeightInfo = pallet_collective::weights::SubstrateWeight<Runtime>;
+    /// Members only change through the membership pallet
+    type SetMembersOrigin = EnsureNever<()>;
+    type MaxProposalWeight = MaxProposalWeight;
+    type DisapproveOrigin = CouncilTwoThirds;
+    type KillOrigin = CouncilTwoThirds;
+    type Consideration = ();
+}
+
+impl pallet_membership::Config<pallet_membership::Instance1> for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+    /// Root can add members and reset the council, which seeds it on chains upgraded from a
+    /// runtime without one. Only the council removes or swaps its members
+    type AddOrigin = RootOrCouncilTwoThirds;
+    type RemoveOrigin = CouncilTwoThirds;
+    type SwapOrigin = CouncilTwoThirds;
+    type ResetOrigin = RootOrCouncilTwoThirds;
+    type PrimeOrigin = CouncilTwoThirds;
+    type MembershipInitialized = Council;
+    type MembershipChanged = Council;
+    type MaxMembers = CouncilMaxMembers;
+    type WeightInfo = pallet_membership::weights::SubstrateWeight<Runtime>;
+}
+
+parameter_types! {
+    pub MaximumSchedulerWeight: Weight = Perbill::from_percent(80) * RuntimeBlockWeights::get().max_block;
+}
+
+impl pallet_scheduler::Config for Runtime {
+    type RuntimeEvent = RuntimeEvent;
+    type RuntimeOrigin = RuntimeOrigin;
+    type PalletsOrigin = OriginCaller;
+    type RuntimeCall = RuntimeCall;
+    type MaximumWeight = MaximumSchedulerWeight;
+    /// Only root schedules arbitrary calls, pallets schedule their own
+    type ScheduleOrigin = EnsureRoot<AccountId>;
+    type MaxScheduledPerBlock = ConstU32<50>;
+    type WeightInfo = pallet_scheduler::weights::SubstrateWeight<Runtime>;
+    type OriginPrivilegeCmp = EqualPrivilegeOnly;
+    /// Scheduled calls are small enough to be stored inline
+    type Preimages = ();
+    type BlockNumberProvider = System;
 }
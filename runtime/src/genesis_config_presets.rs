@@ -15,20 +15,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::{AccountId, BalancesConfig, RuntimeGenesisConfig, SudoConfig};
+use crate::{
+	AccountId, BalancesConfig, CouncilMembershipConfig, NodeAllowlistConfig, RuntimeGenesisConfig,
+	SessionConfig, SessionKeys, SudoConfig, ValidatorSetConfig,
+};
 use alloc::{vec, vec::Vec};
-use frame_support::build_struct_json_patch;
+use frame_support::{build_struct_json_patch, BoundedVec};
 use serde_json::Value;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_consensus_grandpa::AuthorityId as GrandpaId;
 use sp_genesis_builder::{self, PresetId};
 use sp_keyring::Sr25519Keyring;
 
+/// Preset of a local testnet that only accepts the nodes of Alice and Bob.
+pub const PERMISSIONED_RUNTIME_PRESET: &str = "permissioned";
+
+/// Peer id of the node with the ed25519 network key `public`.
+fn peer_id(public: [u8; 32]) -> Vec<u8> {
+	// Identity multihash of the protobuf encoded public key
+	let mut peer_id = vec![0x00, 0x24, 0x08, 0x01, 0x12, 0x20];
+	peer_id.extend_from_slice(&public);
+	peer_id
+}
+
 // Returns the genesis config presets populated with given parameters.
+//
+// Aura and GRANDPA authorities are set by the session pallet from the keys of the validators.
 fn testnet_genesis(
-	initial_authorities: Vec<(AuraId, GrandpaId)>,
+	initial_authorities: Vec<(AccountId, AuraId, GrandpaId)>,
 	endowed_accounts: Vec<AccountId>,
 	root: AccountId,
+	council: Vec<AccountId>,
+	allowed_nodes: Vec<Vec<u8>>,
 ) -> Value {
 	build_struct_json_patch!(RuntimeGenesisConfig {
 		balances: BalancesConfig {
@@ -38,13 +56,24 @@ fn testnet_genesis(
 				.map(|k| (k, 1u128 << 60))
 				.collect::<Vec<_>>(),
 		},
-		aura: pallet_aura::GenesisConfig {
-			authorities: initial_authorities.iter().map(|x| (x.0.clone())).collect::<Vec<_>>(),
+		validator_set: ValidatorSetConfig {
+			validators: initial_authorities.iter().map(|x| x.0.clone()).collect::<Vec<_>>(),
 		},
-		grandpa: pallet_grandpa::GenesisConfig {
-			authorities: initial_authorities.iter().map(|x| (x.1.clone(), 1)).collect::<Vec<_>>(),
+		session: SessionConfig {
+			keys: initial_authorities
+				.iter()
+				.map(|(account, aura, grandpa)| {
+					(
+						account.clone(),
+						account.clone(),
+						SessionKeys { aura: aura.clone(), grandpa: grandpa.clone() },
+					)
+				})
+				.collect::<Vec<_>>(),
 		},
 		sudo: SudoConfig { key: Some(root) },
+		council_membership: CouncilMembershipConfig { members: BoundedVec::truncate_from(council) },
+		node_allowlist: NodeAllowlistConfig { nodes: allowed_nodes },
 	})
 }
 
@@ -52,6 +81,7 @@ fn testnet_genesis(
 pub fn development_config_genesis() -> Value {
 	testnet_genesis(
 		vec![(
+			Sr25519Keyring::Alice.to_account_id(),
 			sp_keyring::Sr25519Keyring::Alice.public().into(),
 			sp_keyring::Ed25519Keyring::Alice.public().into(),
 		)],
@@ -62,18 +92,45 @@ pub fn development_config_genesis() -> Value {
 			Sr25519Keyring::BobStash.to_account_id(),
 		],
 		sp_keyring::Sr25519Keyring::Alice.to_account_id(),
+		vec![Sr25519Keyring::Alice.to_account_id()],
+		vec![],
 	)
 }
 
 /// Return the local genesis config preset.
 pub fn local_config_genesis() -> Value {
+	local_testnet_genesis(vec![])
+}
+
+/// Return the permissioned genesis config preset.
+///
+/// Only the nodes started with the development network keys of Alice
+/// (`c12b6d18942f5ee8528c8e2baf4e147b5c5c18710926ea492d09cbd9f6c9f82a`) and Bob
+/// (`6ce3be907dbcabf20a9a5a60a712b4256a54196000a8ed4050d352bc113f8c58`) are allowed.
+pub fn permissioned_config_genesis() -> Value {
+	local_testnet_genesis(vec![
+		// 12D3KooWBmAwcd4PJNJvfV89HwE48nwkRmAgo8Vy3uQEyNNHBox2
+		peer_id(sp_core::hex2array!(
+			"1ce5f00ef6e89374afb625f1ae4c1546d31234e87e3c3f51a62b91dd6bfa57df"
+		)),
+		// 12D3KooWQYV9dGMFoRzNStwpXztXaBUjtPqi6aU76ZgUriHhKust
+		peer_id(sp_core::hex2array!(
+			"dacde7714d8551f674b8bb4b54239383c76a2b286fa436e93b2b7eb226bf4de7"
+		)),
+	])
+}
+
+// The local testnet, which only accepts `allowed_nodes` when there are any.
+fn local_testnet_genesis(allowed_nodes: Vec<Vec<u8>>) -> Value {
 	testnet_genesis(
 		vec![
 			(
+				Sr25519Keyring::Alice.to_account_id(),
 				sp_keyring::Sr25519Keyring::Alice.public().into(),
 				sp_keyring::Ed25519Keyring::Alice.public().into(),
 			),
 			(
+				Sr25519Keyring::Bob.to_account_id(),
 				sp_keyring::Sr25519Keyring::Bob.public().into(),
 				sp_keyring::Ed25519Keyring::Bob.public().into(),
 			),
@@ -83,6 +140,12 @@ pub fn local_config_genesis() -> Value {
 			.map(|v| v.to_account_id())
 			.collect::<Vec<_>>(),
 		Sr25519Keyring::Alice.to_account_id(),
+		vec![
+			Sr25519Keyring::Alice.to_account_id(),
+			Sr25519Keyring::Bob.to_account_id(),
+			Sr25519Keyring::Charlie.to_account_id(),
+		],
+		allowed_nodes,
 	)
 }
 
@@ -91,6 +154,7 @@ pub fn get_preset(id: &PresetId) -> Option<Vec<u8>> {
 	let patch = match id.as_ref() {
 		sp_genesis_builder::DEV_RUNTIME_PRESET => development_config_genesis(),
 		sp_genesis_builder::LOCAL_TESTNET_RUNTIME_PRESET => local_config_genesis(),
+		PERMISSIONED_RUNTIME_PRESET => permissioned_config_genesis(),
 		_ => return None,
 	};
 	Some(
@@ -105,5 +169,6 @@ pub fn preset_names() -> Vec<PresetId> {
 	vec![
 		PresetId::from(sp_genesis_builder::DEV_RUNTIME_PRESET),
 		PresetId::from(sp_genesis_builder::LOCAL_TESTNET_RUNTIME_PRESET),
+		PresetId::from(PERMISSIONED_RUNTIME_PRESET),
 	]
 }
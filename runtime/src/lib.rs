@@ -7,6 +7,7 @@ pub mod apis;
 #[cfg(feature = "runtime-benchmarks")]
 mod benchmarks;
 pub mod configs;
+mod migrations;
 
 extern crate alloc;
 use alloc::vec::Vec;
@@ -67,12 +68,14 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     // The version of the runtime specification. A full node will not attempt to use its native
     //   runtime in substitute for the on-chain Wasm runtime unless all of `spec_name`,
     //   `spec_version`, and `authoring_version` are the same between Wasm and native.
-    // This value is set to 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use
-    //   the compatible custom types.
-    spec_version: 100,
+    // It started at 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use the
+    //   compatible custom types. Increase it with every runtime change: `set_code` rejects code
+    //   that does not, and the pallet migrations only run on a new `spec_version`.
+    spec_version: 102,
     impl_version: 1,
     apis: apis::RUNTIME_API_VERSIONS,
-    transaction_version: 1,
+    // Increase it whenever `TxExtension` or the encoding of existing calls changes.
+    transaction_version: 3,
     system_version: 1,
 };
 
@@ -134,7 +137,8 @@ pub type Hash = sp_core::H256;
 /// An index to a block.
 pub type BlockNumber = u32;
 
-/// The address format for describing accounts.
+/// The address format for describing accounts. `MultiAddress::Raw` carries a username, see
+/// [`pallet_username_storage::UsernameLookup`].
 pub type Address = MultiAddress<AccountId, ()>;
 
 /// Block header type as expected by this runtime.
@@ -149,6 +153,13 @@ pub type SignedBlock = generic::SignedBlock<Block>;
 /// BlockId type as expected by this runtime.
 pub type BlockId = generic::BlockId<Block>;
 
+/// Pool and block level checks of `set_username`, one per username registry.
+pub type CheckUsernames = (
+    pallet_username_storage::CheckUsername<Runtime>,
+    pallet_username_storage::CheckUsername<Runtime, configs::BotRegistry>,
+    pallet_username_storage::CheckUsername<Runtime, configs::OrganizationRegistry>,
+);
+
 /// The `TransactionExtension` to the basic transaction logic.
 pub type TxExtension = (
     frame_system::CheckNonZeroSender<Runtime>,
@@ -156,9 +167,15 @@ pub type TxExtension = (
     frame_system::CheckTxVersion<Runtime>,
     frame_system::CheckGenesis<Runtime>,
     frame_system::CheckEra<Runtime>,
+    // Reads the nonce of `CheckNonce`, so it must come right before it.
+    CheckUsernames,
     frame_system::CheckNonce<Runtime>,
     frame_system::CheckWeight<Runtime>,
-    pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+    pallet_username_storage::CheckSponsoredRegistration<Runtime>,
+    pallet_skip_feeless_payment::SkipCheckIfFeeless<
+        Runtime,
+        pallet_transaction_payment::ChargeTransactionPayment<Runtime>,
+    >,
     frame_metadata_hash_extension::CheckMetadataHash<Runtime>,
     frame_system::WeightReclaim<Runtime>,
 );
@@ -174,7 +191,11 @@ pub type SignedPayload = generic::SignedPayload<RuntimeCall, TxExtension>;
 ///
 /// This can be a tuple of types, each implementing `OnRuntimeUpgrade`.
 #[allow(unused_parens)]
-type Migrations = ();
+type Migrations = (
+    pallet_username_storage::migrations::v1::BackfillUsernameOwners<Runtime>,
+    pallet_username_storage::migrations::v2::InitUsernameCount<Runtime>,
+    migrations::InitValidators,
+);
 
 /// Executive: handles dispatch to the various modules.
 pub type Executive = frame_executive::Executive<
@@ -210,6 +231,14 @@ mod runtime {
     #[runtime::pallet_index(1)]
     pub type Timestamp = pallet_timestamp;
 
+    // The validator set and session pallets are declared before Aura and GRANDPA, so that genesis
+    // and every session rotation hand the new authorities to them first.
+    #[runtime::pallet_index(10)]
+    pub type ValidatorSet = pallet_validator_set;
+
+    #[runtime::pallet_index(11)]
+    pub type Session = pallet_session;
+
     #[runtime::pallet_index(2)]
     pub type Aura = pallet_aura;
 
@@ -231,4 +260,27 @@ mod runtime {
 
     #[runtime::pallet_index(8)]
     pub type UsernameStorage = pallet_username_storage;
+
+    #[runtime::pallet_index(9)]
+    pub type SkipFeelessPayment = pallet_skip_feeless_payment;
+
+    #[runtime::pallet_index(12)]
+    pub type NodeAllowlist = pallet_node_allowlist;
+
+    // The council moderating usernames, and the management of its members.
+    #[runtime::pallet_index(13)]
+    pub type Council = pallet_collective<Instance1>;
+
+    #[runtime::pallet_index(14)]
+    pub type CouncilMembership = pallet_membership<Instance1>;
+
+    #[runtime::pallet_index(15)]
+    pub type Scheduler = pallet_scheduler;
+
+    // Username registries of bots and organizations, next to the users one of `UsernameStorage`.
+    #[runtime::pallet_index(16)]
+    pub type BotUsernames = pallet_username_storage<Instance1>;
+
+    #[runtime::pallet_index(17)]
+    pub type OrganizationUsernames = pallet_username_storage<Instance2>;
 }
@@ -0,0 +1,197 @@
+//! Runtime migrations spanning several pallets.
+
+use crate::{AccountId, Runtime, SessionKeys};
+use alloc::vec::Vec;
+use frame_support::{
+    traits::{Get, OnRuntimeUpgrade},
+    weights::Weight,
+    BoundedVec,
+};
+use sp_runtime::traits::OpaqueKeys;
+
+/// Seed the validator set and the session keys from the current Aura and GRANDPA authorities.
+///
+/// Chains started before the validator set and session pallets have their authorities in Aura
+/// and GRANDPA only. Without this migration the first session rotation would find no validators
+/// nor keys. The authorities of both pallets are paired in order, as genesis sets them, and each
+/// validator account is the one of its Aura key.
+///
+/// FRAME sets the storage version of a new pallet to its in-code version before any migration
+/// runs, so the migration cannot be guarded by one. It only runs while `ValidatorSet` has no
+/// validators, which never happens once it has been seeded.
+pub struct InitValidators;
+
+impl OnRuntimeUpgrade for InitValidators {
+    fn on_runtime_upgrade() -> Weight {
+        let db_weight = <Runtime as frame_system::Config>::DbWeight::get();
+        if !pallet_validator_set::Validators::<Runtime>::get().is_empty() {
+            return db_weight.reads(1);
+        }
+
+        let grandpa = pallet_grandpa::Pallet::<Runtime>::grandpa_authorities();
+        let keys: Vec<(AccountId, SessionKeys)> = pallet_aura::Authorities::<Runtime>::get()
+            .into_iter()
+            .zip(grandpa)
+            .map(|(aura, (grandpa, _))| {
+                let who = AccountId::from(sp_core::sr25519::Public::from(aura.clone()));
+                (who, SessionKeys { aura, grandpa })
+            })
+            .collect();
+        let validators: Vec<AccountId> = keys.iter().map(|(who, _)| who.clone()).collect();
+
+        for (who, keys) in &keys {
+            // As the genesis of the session pallet does, the keys keep the account alive.
+            if !frame_system::Pallet::<Runtime>::account_exists(who) {
+                frame_system::Pallet::<Runtime>::inc_providers(who);
+            }
+            let _ = frame_system::Pallet::<Runtime>::inc_consumers(who);
+            for id in SessionKeys::key_ids() {
+                pallet_session::KeyOwner::<Runtime>::insert((*id, keys.get_raw(*id).to_vec()), who);
+            }
+            pallet_session::NextKeys::<Runtime>::insert(who, keys);
+        }
+        pallet_session::QueuedKeys::<Runtime>::put(&keys);
+        pallet_session::Validators::<Runtime>::put(&validators);
+        pallet_validator_set::Validators::<Runtime>::put(BoundedVec::truncate_from(validators));
+
+        let count = keys.len() as u64;
+        db_weight.reads_writes(3 + 2 * count, 3 + 5 * count)
+    }
+
+    #[cfg(feature = "try-runtime")]
+    fn post_upgrade(_state: Vec<u8>) -> Result<(), sp_runtime::TryRuntimeError> {
+        let validators = pallet_validator_set::Validators::<Runtime>::get();
+        frame_support::ensure!(!validators.is_empty(), "No validators");
+        for who in validators {
+            frame_support::ensure!(
+                pallet_session::NextKeys::<Runtime>::contains_key(&who),
+                "Validator without session keys"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RuntimeGenesisConfig, SessionConfig, System, ValidatorSetConfig};
+    use frame_support::{storage::unhashed, StorageHasher, Twox128};
+    use sp_consensus_aura::sr25519::AuthorityId as AuraId;
+    use sp_consensus_grandpa::AuthorityId as GrandpaId;
+    use sp_core::crypto::key_types::AURA;
+    use sp_keyring::{Ed25519Keyring, Sr25519Keyring};
+    use sp_runtime::BuildStorage;
+
+    fn session_keys(keyring: Sr25519Keyring, grandpa: Ed25519Keyring) -> SessionKeys {
+        let aura: AuraId = keyring.public().into();
+        let grandpa: GrandpaId = grandpa.public().into();
+        SessionKeys { aura, grandpa }
+    }
+
+    /// The state of a chain started before the validator set and session pallets, with Alice and
+    /// Bob as Aura and GRANDPA authorities.
+    fn new_test_ext() -> sp_io::TestExternalities {
+        let authorities = [
+            (Sr25519Keyring::Alice, Ed25519Keyring::Alice),
+            (Sr25519Keyring::Bob, Ed25519Keyring::Bob),
+        ];
+        let accounts: Vec<AccountId> = authorities
+            .iter()
+            .map(|(keyring, _)| keyring.to_account_id())
+            .collect();
+        let storage = RuntimeGenesisConfig {
+            validator_set: ValidatorSetConfig {
+                validators: accounts.clone(),
+            },
+            session: SessionConfig {
+                keys: authorities
+                    .iter()
+                    .map(|(aura, grandpa)| {
+                        let who = aura.to_account_id();
+                        (who.clone(), who, session_keys(*aura, *grandpa))
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .build_storage()
+        .unwrap();
+
+        let mut ext = sp_io::TestExternalities::new(storage);
+        ext.execute_with(|| {
+            // Only the authorities of Aura and GRANDPA are left
+            for pallet in [&b"ValidatorSet"[..], b"Session"] {
+                let _ = unhashed::clear_prefix(&Twox128::hash(pallet), None, None);
+            }
+            for who in &accounts {
+                System::dec_consumers(who);
+            }
+            System::set_block_number(1);
+        });
+        ext
+    }
+
+    #[test]
+    fn test_init_validators() {
+        new_test_ext().execute_with(|| {
+            let alice = Sr25519Keyring::Alice.to_account_id();
+            let bob = Sr25519Keyring::Bob.to_account_id();
+            let alice_keys = session_keys(Sr25519Keyring::Alice, Ed25519Keyring::Alice);
+            let bob_keys = session_keys(Sr25519Keyring::Bob, Ed25519Keyring::Bob);
+            assert!(pallet_validator_set::Validators::<Runtime>::get().is_empty());
+            assert_eq!(pallet_aura::Authorities::<Runtime>::get().len(), 2);
+
+            InitValidators::on_runtime_upgrade();
+
+            let validators = vec![alice.clone(), bob.clone()];
+            assert_eq!(
+                pallet_validator_set::Validators::<Runtime>::get().into_inner(),
+                validators
+            );
+            assert_eq!(pallet_session::Validators::<Runtime>::get(), validators);
+            assert_eq!(
+                pallet_session::QueuedKeys::<Runtime>::get(),
+                vec![(alice.clone(), alice_keys.clone()), (bob.clone(), bob_keys)]
+            );
+            assert_eq!(
+                pallet_session::NextKeys::<Runtime>::get(&alice),
+                Some(alice_keys.clone())
+            );
+            assert_eq!(
+                pallet_session::KeyOwner::<Runtime>::get((AURA, alice_keys.get_raw(AURA).to_vec())),
+                Some(alice.clone())
+            );
+            assert_eq!(System::consumers(&alice), 1);
+
+            // Later upgrades leave the validators alone
+            pallet_validator_set::Validators::<Runtime>::put(BoundedVec::truncate_from(vec![
+                alice.clone(),
+            ]));
+            InitValidators::on_runtime_upgrade();
+            assert_eq!(
+                pallet_validator_set::Validators::<Runtime>::get().into_inner(),
+                vec![alice]
+            );
+        });
+    }
+
+    #[test]
+    fn test_session_rotation_after_init() {
+        new_test_ext().execute_with(|| {
+            let authorities = pallet_aura::Authorities::<Runtime>::get();
+            InitValidators::on_runtime_upgrade();
+
+            pallet_session::Pallet::<Runtime>::rotate_session();
+            pallet_session::Pallet::<Runtime>::rotate_session();
+
+            // The seeded keys keep the same authorities
+            assert_eq!(pallet_aura::Authorities::<Runtime>::get(), authorities);
+            assert_eq!(
+                pallet_grandpa::Pallet::<Runtime>::grandpa_authorities().len(),
+                authorities.len()
+            );
+        });
+    }
+}
@@ -0,0 +1,62 @@
+#!/bin/bash
+
+# Run the two validators of the permissioned testnet. Only the nodes with the network keys below
+# are accepted, and RPC is only served on localhost.
+
+ALICE_NODE_KEY=c12b6d18942f5ee8528c8e2baf4e147b5c5c18710926ea492d09cbd9f6c9f82a
+ALICE_PEER_ID=12D3KooWBmAwcd4PJNJvfV89HwE48nwkRmAgo8Vy3uQEyNNHBox2
+BOB_NODE_KEY=6ce3be907dbcabf20a9a5a60a712b4256a54196000a8ed4050d352bc113f8c58
+
+# Build the node
+echo "Building the node..."
+cargo build --release
+
+# Kill any existing nodes
+echo "Cleaning up existing nodes..."
+pkill -f solochain-template-node || true
+
+# Create base paths and clean them
+echo "Setting up node directories..."
+rm -rf /tmp/permissioned01 /tmp/permissioned02
+mkdir -p /tmp/permissioned01
+mkdir -p /tmp/permissioned02
+
+# Start Node A (Validator)
+echo "Starting Node A (Validator)..."
+./target/release/solochain-template-node \
+  --base-path /tmp/permissioned01 \
+  --chain permissioned \
+  --alice \
+  --port 30333 \
+  --rpc-port 9944 \
+  --validator \
+  --name "Node A" \
+  --node-key "$ALICE_NODE_KEY" \
+  --no-mdns \
+  --no-telemetry &
+
+# Wait for Node A to start
+echo "Waiting for Node A to start..."
+sleep 5
+
+# Start Node B (Validator)
+echo "Starting Node B (Validator)..."
+./target/release/solochain-template-node \
+  --base-path /tmp/permissioned02 \
+  --chain permissioned \
+  --bob \
+  --port 30334 \
+  --rpc-port 9945 \
+  --validator \
+  --bootnodes "/ip4/127.0.0.1/tcp/30333/p2p/$ALICE_PEER_ID" \
+  --name "Node B" \
+  --node-key "$BOB_NODE_KEY" \
+  --no-mdns \
+  --no-telemetry &
+
+echo "Nodes started successfully!"
+echo "Node A: ws://localhost:9944"
+echo "Node B: ws://localhost:9945"
+
+# Keep the script running
+wait
@@ -26,8 +26,13 @@ async function main() {
         const hash = await tx.signAndSend(alice);
         console.log('Transaction hash:', hash.toHex());
 
-        // Wait for the transaction to be included in a block
-        await new Promise(resolve => setTimeout(resolve, 5000));
+        // Seal a block right away on nodes started with `--sealing manual`, otherwise wait for
+        // the transaction to be included in the next block
+        try {
+            await api.rpc.engine.createBlock(true, true);
+        } catch (error) {
+            await new Promise(resolve => setTimeout(resolve, 6000));
+        }
 
         // Get username using the storage query
         console.log('Getting username...');
@@ -0,0 +1,41 @@
+#!/bin/bash
+# Test a runtime upgrade against a snapshot of a live chain.
+#
+# Usage: scripts/try_runtime.sh [ws://node:9944]
+#
+# Environment:
+#   SNAPSHOT                    snapshot file, taken from the node when missing
+#   DISABLE_SPEC_VERSION_CHECK  set to 1 to test a runtime whose spec_version is not above the
+#                               live one, for example to re-run the checks of an applied upgrade
+#   EXECUTE_BLOCK               set to 1 to also re-execute the latest live block, once the live
+#                               chain runs this runtime and its migrations are applied
+#
+# Requires the standalone `try-runtime` CLI:
+#   cargo install --git https://github.com/paritytech/try-runtime-cli --locked
+set -e
+
+URI=${1:-ws://127.0.0.1:9944}
+SNAPSHOT=${SNAPSHOT:-target/live.snap}
+RUNTIME=target/release/wbuild/solochain-template-runtime/solochain_template_runtime.compact.compressed.wasm
+
+UPGRADE_FLAGS=()
+if [ "$DISABLE_SPEC_VERSION_CHECK" = "1" ]; then
+    UPGRADE_FLAGS+=(--disable-spec-version-check)
+fi
+
+echo "Building the runtime with try-runtime checks..."
+cargo build --release -p solochain-template-runtime --features try-runtime
+
+if [ ! -f "$SNAPSHOT" ]; then
+    echo "Taking a snapshot of $URI..."
+    try-runtime create-snapshot --uri "$URI" "$SNAPSHOT"
+fi
+
+echo "Running the migrations and try_state checks on the snapshot..."
+try-runtime --runtime "$RUNTIME" on-runtime-upgrade --blocktime 6000 --checks all \
+    "${UPGRADE_FLAGS[@]}" snap --path "$SNAPSHOT"
+
+if [ "$EXECUTE_BLOCK" = "1" ]; then
+    echo "Re-executing the latest block with the new runtime..."
+    try-runtime --runtime "$RUNTIME" execute-block --try-state all live --uri "$URI"
+fi
@@ -0,0 +1,188 @@
+//! In-process test network for the username chain.
+//!
+//! [`TestNetwork::start`] runs several nodes of the `local` chain inside the calling test, each
+//! with its own temporary base path and free ports. The first two nodes are the Alice and Bob
+//! validators of the chain spec, any further node is a full node. Every node bootstraps from the
+//! first one and is driven over its WebSocket RPC, like an external client would.
+//!
+//! Nodes run on the Tokio runtime of the test, which has to be multi-threaded:
+//!
+//! ```ignore
+//! #[tokio::test(flavor = "multi_thread")]
+//! async fn test() {
+//!     let network = TestNetwork::start(3).await.unwrap();
+//!     network.wait_for_peers(Duration::from_secs(60)).await.unwrap();
+//! }
+//! ```
+
+use jsonrpsee::{core::client::ClientT, rpc_params};
+use sc_cli::SubstrateCli;
+use sc_service::TaskManager;
+use serde_json::Value;
+use solochain_template_node::{cli::Cli, service};
+use solochain_template_runtime::Hash;
+use std::{future::Future, net::TcpListener, time::Duration};
+use tempfile::TempDir;
+use tokio::time::Instant;
+use username_client::UsernameClient;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Development accounts of the validators of the `local` chain, in node order.
+const VALIDATORS: [&str; 2] = ["--alice", "--bob"];
+
+/// Interval between two checks of [`wait_for`].
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A free TCP port on the loopback interface.
+fn free_port() -> Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+/// Poll `check` until it returns a value, or fail once `timeout` has elapsed.
+pub async fn wait_for<T, F, Fut>(what: &str, timeout: Duration, mut check: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Option<T>>>,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(value) = check().await? {
+            return Ok(value);
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("Timed out waiting for {}", what).into());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// A node running in the test process.
+pub struct TestNode {
+    /// Name of the node
+    pub name: String,
+    /// WebSocket RPC endpoint of the node
+    pub rpc_url: String,
+    p2p_port: u16,
+    client: UsernameClient,
+    // Dropped before the base path, which stops the node.
+    _task_manager: TaskManager,
+    _base_path: TempDir,
+}
+
+impl TestNode {
+    /// Start node number `index`, connecting to `bootnode` when given.
+    pub async fn start(index: usize, bootnode: Option<&str>) -> Result<Self> {
+        let base_path = tempfile::tempdir()?;
+        let name = format!("node-{}", index);
+        let (p2p_port, rpc_port) = (free_port()?, free_port()?);
+
+        let mut args = vec![
+            "solochain-template-node".to_string(),
+            "--chain=local".into(),
+            format!("--base-path={}", base_path.path().display()),
+            format!("--name={}", name),
+            format!("--listen-addr=/ip4/127.0.0.1/tcp/{}", p2p_port),
+            format!("--rpc-port={}", rpc_port),
+            // Deterministic keys keep peer ids stable across runs.
+            format!("--node-key={:064x}", index + 1),
+            "--no-mdns".into(),
+            "--no-prometheus".into(),
+            "--no-telemetry".into(),
+        ];
+        if let Some(validator) = VALIDATORS.get(index) {
+            args.push(validator.to_string());
+        }
+        if let Some(bootnode) = bootnode {
+            args.push(format!("--bootnodes={}", bootnode));
+        }
+
+        let cli = Cli::from_iter(args);
+        let config = cli.create_configuration(&cli.run, tokio::runtime::Handle::current())?;
+        let task_manager = service::new_full_node(config, cli.username.clone(), cli.sealing)?;
+
+        let rpc_url = format!("ws://127.0.0.1:{}", rpc_port);
+        let client = UsernameClient::connect(&rpc_url).await?;
+        Ok(Self {
+            name,
+            rpc_url,
+            p2p_port,
+            client,
+            _task_manager: task_manager,
+            _base_path: base_path,
+        })
+    }
+
+    /// Client connected to the RPC of the node.
+    pub fn client(&self) -> &UsernameClient {
+        &self.client
+    }
+
+    /// The address other nodes can reach this node at, including its peer id.
+    pub async fn p2p_address(&self) -> Result<String> {
+        let peer_id: String = self
+            .client
+            .rpc()
+            .request("system_localPeerId", rpc_params![])
+            .await?;
+        Ok(format!(
+            "/ip4/127.0.0.1/tcp/{}/p2p/{}",
+            self.p2p_port, peer_id
+        ))
+    }
+
+    /// Number of peers the node is connected to.
+    pub async fn peers(&self) -> Result<u64> {
+        let health: Value = self
+            .client
+            .rpc()
+            .request("system_health", rpc_params![])
+            .await?;
+        health["peers"]
+            .as_u64()
+            .ok_or_else(|| "Invalid `system_health` response".into())
+    }
+
+    /// Hash of the last finalized block.
+    pub async fn finalized_head(&self) -> Result<Hash> {
+        Ok(self
+            .client
+            .rpc()
+            .request("chain_getFinalizedHead", rpc_params![])
+            .await?)
+    }
+}
+
+/// Several nodes of the same chain, running in the test process.
+pub struct TestNetwork {
+    pub nodes: Vec<TestNode>,
+}
+
+impl TestNetwork {
+    /// Start `count` nodes, all bootstrapping from the first one.
+    pub async fn start(count: usize) -> Result<Self> {
+        let first = TestNode::start(0, None).await?;
+        let bootnode = first.p2p_address().await?;
+
+        let mut nodes = vec![first];
+        for index in 1..count {
+            nodes.push(TestNode::start(index, Some(&bootnode)).await?);
+        }
+        Ok(Self { nodes })
+    }
+
+    /// Wait until every node is connected to every other node.
+    pub async fn wait_for_peers(&self, timeout: Duration) -> Result<()> {
+        let expected = self.nodes.len() as u64 - 1;
+        for node in &self.nodes {
+            wait_for(
+                &format!("{} to connect", node.name),
+                timeout,
+                || async move { Ok((node.peers().await? >= expected).then_some(())) },
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
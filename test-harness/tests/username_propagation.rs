@@ -0,0 +1,46 @@
+//! End to end test of a username going through the network, consensus and the custom RPC.
+
+use sp_keyring::Sr25519Keyring;
+use std::time::Duration;
+use username_client::Username;
+use username_test_harness::{wait_for, TestNetwork};
+
+/// Both validators of the `local` chain and one full node.
+const NODES: usize = 3;
+/// Long enough for a few 6 second blocks and their finalization.
+const TIMEOUT: Duration = Duration::from_secs(180);
+
+#[tokio::test(flavor = "multi_thread")]
+async fn username_propagates_and_finalizes() {
+    let network = TestNetwork::start(NODES).await.expect("Nodes start");
+    network
+        .wait_for_peers(TIMEOUT)
+        .await
+        .expect("Nodes connect to each other");
+
+    // Submit through the full node, so the transaction has to be gossiped to a validator.
+    let alice = Sr25519Keyring::Alice;
+    let full_node = network.nodes.last().unwrap();
+    full_node
+        .client()
+        .set_username(&alice.pair(), b"alice".to_vec())
+        .await
+        .expect("Transaction is accepted");
+
+    for node in &network.nodes {
+        wait_for(
+            &format!("the username to finalize on {}", node.name),
+            TIMEOUT,
+            || async move {
+                let finalized = node.finalized_head().await?;
+                let username = node
+                    .client()
+                    .username(alice.to_account_id(), Some(finalized))
+                    .await?;
+                Ok((username == Some(Username::Utf8("alice".into()))).then_some(()))
+            },
+        )
+        .await
+        .expect("Username is finalized");
+    }
+}